@@ -0,0 +1,77 @@
+//! Adaptive polling: longer intervals on battery, while the machine is idle or
+//! while the reader of stdout lags behind, and full rate again on activity.
+//!
+//! Enabled with `adaptive = true` in the config or `--adaptive on`.
+
+use std::time::{Duration, Instant};
+
+use crate::collector::Activity;
+
+/// Idle has to last this long before polling slows down
+pub const IDLE_AFTER: Duration = Duration::from_secs(10);
+/// Interval multiplier while running on battery
+const BATTERY_SLOWDOWN: u32 = 2;
+/// Interval multiplier while idle, on top of the battery one
+const IDLE_SLOWDOWN: u32 = 4;
+/// Largest multiplier, also used while the reader is blocked
+pub const MAX_SLOWDOWN: u32 = 8;
+
+/// Tracks how long the machine has been idle
+#[derive(Default)]
+pub struct Adaptive {
+    /// Start of the current idle stretch, `None` while busy
+    idle_since: Option<Instant>,
+}
+
+impl Adaptive {
+    /// Interval multiplier for the conditions at `now`; a busy sample drops it back to 1.
+    pub fn slowdown(&mut self, now: Instant, activity: Activity, on_battery: bool, blocked: bool) -> u32 {
+        match activity {
+            Activity::Busy => self.idle_since = None,
+            Activity::Idle => {
+                self.idle_since.get_or_insert(now);
+            }
+            Activity::Unknown => {}
+        }
+        if blocked {
+            return MAX_SLOWDOWN;
+        }
+
+        let mut factor = 1;
+        if on_battery {
+            factor *= BATTERY_SLOWDOWN;
+        }
+        if self.idle_since.is_some_and(|since| now.duration_since(since) >= IDLE_AFTER) {
+            factor *= IDLE_SLOWDOWN;
+        }
+        factor.min(MAX_SLOWDOWN)
+    }
+}
+
+/// Whether writing to stdout would block: the reader stopped consuming (e.g.,
+/// eww is stopped) and the pipe is full. Files and terminals never block.
+pub fn stdout_blocked() -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDOUT_FILENO,
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, 0) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slows_down_when_idle_and_recovers_on_activity() {
+        let start = Instant::now();
+        let mut adaptive = Adaptive::default();
+        assert_eq!(adaptive.slowdown(start, Activity::Idle, false, false), 1);
+        assert_eq!(adaptive.slowdown(start + IDLE_AFTER, Activity::Idle, false, false), IDLE_SLOWDOWN);
+        assert_eq!(adaptive.slowdown(start + IDLE_AFTER, Activity::Idle, true, false), MAX_SLOWDOWN);
+        assert_eq!(adaptive.slowdown(start + IDLE_AFTER * 2, Activity::Busy, true, false), BATTERY_SLOWDOWN);
+        assert_eq!(adaptive.slowdown(start + IDLE_AFTER * 2, Activity::Idle, false, false), 1);
+        assert_eq!(adaptive.slowdown(start + IDLE_AFTER * 2, Activity::Busy, false, true), MAX_SLOWDOWN);
+    }
+}
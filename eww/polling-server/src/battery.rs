@@ -0,0 +1,232 @@
+//! Battery charge, AC adapter state and charge control thresholds from
+//! `/sys/class/power_supply`.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::collector::Collector;
+use crate::source::Roots;
+use crate::{parse_number_from_line, pread_file, push_json_str, push_opt_u32};
+
+/// Directory holding power supplies (AC, ADP1, BAT0, ...)
+const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+/// Threshold attributes (ThinkPads, ASUS, Framework, recent Dell/LG)
+const START_THRESHOLD: &str = "charge_control_start_threshold";
+const END_THRESHOLD: &str = "charge_control_end_threshold";
+/// Supply types that power the machine when online: AC adapters and USB(-C)
+/// chargers, e.g. the UCSI `ucsi-source-psy-*` ports of USB-C-only laptops
+const ADAPTER_TYPES: &[&str] = &["Mains", "USB", "USB_C", "USB_PD", "USB_PD_DRP"];
+/// Battery status while nothing powers the machine
+const DISCHARGING: &str = "Discharging";
+
+/// Open files of the system battery and AC adapters
+#[derive(Default)]
+pub struct PowerSupplyFiles {
+    battery: Option<BatteryFiles>,
+    /// `online` of every AC adapter and USB charger
+    adapters: Vec<File>,
+}
+
+struct BatteryFiles {
+    capacity: File,
+    status: File,
+    start_threshold: Option<File>,
+    end_threshold: Option<File>,
+}
+
+/// Battery and AC entry for output
+pub struct BatteryEntry {
+    /// Charge percentage, `None` on machines without a system battery
+    pub capacity: Option<u32>,
+    /// Kernel status string ("Charging", "Discharging", "Not charging", "Full")
+    pub status: Option<String>,
+    /// Whether any AC adapter or USB charger is online; without either, whether
+    /// the battery is not discharging
+    pub ac_online: bool,
+    /// Charging starts below this percentage
+    pub start_threshold: Option<u32>,
+    /// Charging stops at this percentage
+    pub end_threshold: Option<u32>,
+}
+
+fn supply_type(path: &Path) -> Option<String> {
+    Some(fs::read_to_string(path.join("type")).ok()?.trim().to_string())
+}
+
+/// System batteries; peripheral ones (mice, headsets) report scope "Device".
+fn system_batteries(roots: &Roots) -> Vec<PathBuf> {
+    let Ok(dir) = fs::read_dir(roots.resolve(POWER_SUPPLY_PATH)) else {
+        return Vec::new();
+    };
+    let mut batteries: Vec<PathBuf> = dir
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| supply_type(path).as_deref() == Some("Battery"))
+        .filter(|path| {
+            fs::read_to_string(path.join("scope")).map_or(true, |scope| scope.trim() != "Device")
+        })
+        .collect();
+    batteries.sort();
+    batteries
+}
+
+/// Open the first system battery and all AC adapters and USB chargers.
+pub fn discover(roots: &Roots) -> PowerSupplyFiles {
+    let battery = system_batteries(roots).first().and_then(|path| {
+        Some(BatteryFiles {
+            capacity: File::open(path.join("capacity")).ok()?,
+            status: File::open(path.join("status")).ok()?,
+            start_threshold: File::open(path.join(START_THRESHOLD)).ok(),
+            end_threshold: File::open(path.join(END_THRESHOLD)).ok(),
+        })
+    });
+    let adapters = fs::read_dir(roots.resolve(POWER_SUPPLY_PATH))
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| supply_type(path).is_some_and(|kind| ADAPTER_TYPES.contains(&kind.as_str())))
+                .filter_map(|path| File::open(path.join("online")).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    PowerSupplyFiles { battery, adapters }
+}
+
+#[inline]
+fn read_number(file: &File, buf: &mut [u8]) -> Option<u32> {
+    let len = pread_file(file.as_raw_fd(), buf).ok()?;
+    Some(parse_number_from_line(&buf[..len]) as u32)
+}
+
+/// Read battery and AC state; `None` when the machine has neither.
+#[inline]
+pub fn collect_battery(files: &PowerSupplyFiles, buf: &mut [u8]) -> Option<BatteryEntry> {
+    if files.battery.is_none() && files.adapters.is_empty() {
+        return None;
+    }
+    let ac_online = files.adapters.iter().any(|file| read_number(file, buf) == Some(1));
+
+    let Some(battery) = &files.battery else {
+        return Some(BatteryEntry {
+            capacity: None,
+            status: None,
+            ac_online,
+            start_threshold: None,
+            end_threshold: None,
+        });
+    };
+    let status = pread_file(battery.status.as_raw_fd(), buf)
+        .ok()
+        .and_then(|len| std::str::from_utf8(&buf[..len]).ok().map(|s| s.trim().to_string()));
+    // Without a supply to ask, only a discharging battery means running on it
+    let ac_online = if files.adapters.is_empty() { status.as_deref() != Some(DISCHARGING) } else { ac_online };
+    Some(BatteryEntry {
+        capacity: read_number(&battery.capacity, buf),
+        status,
+        ac_online,
+        start_threshold: battery.start_threshold.as_ref().and_then(|file| read_number(file, buf)),
+        end_threshold: battery.end_threshold.as_ref().and_then(|file| read_number(file, buf)),
+    })
+}
+
+/// Set charge thresholds on every system battery that supports them.
+/// Needs write access to sysfs, i.e. root or a udev rule.
+pub fn set_thresholds(roots: &Roots, start: u32, end: u32) -> io::Result<()> {
+    if start >= end || end > 100 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid thresholds {}-{}, expected start < end <= 100", start, end),
+        ));
+    }
+    let batteries: Vec<PathBuf> = system_batteries(roots)
+        .into_iter()
+        .filter(|path| path.join(END_THRESHOLD).exists())
+        .collect();
+    if batteries.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no battery supports charge thresholds"));
+    }
+
+    for path in batteries {
+        // The kernel rejects start >= end at every step, so raise end first when moving up
+        let current_end = fs::read(path.join(END_THRESHOLD)).map_or(100, |data| parse_number_from_line(&data) as u32);
+        let has_start = path.join(START_THRESHOLD).exists();
+        if start >= current_end {
+            fs::write(path.join(END_THRESHOLD), end.to_string())?;
+            if has_start {
+                fs::write(path.join(START_THRESHOLD), start.to_string())?;
+            }
+        } else {
+            if has_start {
+                fs::write(path.join(START_THRESHOLD), start.to_string())?;
+            }
+            fs::write(path.join(END_THRESHOLD), end.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Battery charge, AC state and charge thresholds ("b")
+#[derive(Default)]
+pub struct BatteryCollector {
+    files: PowerSupplyFiles,
+    entry: Option<BatteryEntry>,
+    /// Capacities and status strings hold a single value
+    buf: [u8; 32],
+}
+
+impl Collector for BatteryCollector {
+    fn name(&self) -> &'static str {
+        "battery"
+    }
+
+    fn key(&self) -> &'static str {
+        "b"
+    }
+
+    /// Status and AC changes arrive as uevents; capacity is not always announced, so it is polled too
+    fn interval(&self) -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn uevent_subsystem(&self) -> Option<&'static str> {
+        Some("power_supply")
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.files = discover(roots);
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, _elapsed: f64) {
+        self.entry = collect_battery(&self.files, &mut self.buf);
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        let Some(battery) = &self.entry else {
+            out.push_str("null");
+            return;
+        };
+        out.push('[');
+        push_opt_u32(out, battery.capacity);
+        out.push(',');
+        match &battery.status {
+            Some(status) => push_json_str(out, status),
+            None => out.push_str("null"),
+        }
+        out.push_str(if battery.ac_online { ",true," } else { ",false," });
+        push_opt_u32(out, battery.start_threshold);
+        out.push(',');
+        push_opt_u32(out, battery.end_threshold);
+        out.push(']');
+    }
+
+    /// No adapter or charger online, with one or a battery present. Going by their
+    /// state rather than the battery status, which doesn't say "Discharging" on every
+    /// machine; only without any does the status decide.
+    fn on_battery(&self) -> bool {
+        self.entry.as_ref().is_some_and(|battery| !battery.ac_online)
+    }
+}
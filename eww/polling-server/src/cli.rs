@@ -0,0 +1,378 @@
+//! Command-line parsing, help text and shell completions.
+//!
+//! Subcommands and options are listed once in `SUBCOMMANDS` and `OPTIONS`;
+//! the help text and the fish completions are generated from those tables.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::output::Format;
+use crate::source::Roots;
+use crate::Registry;
+
+/// Subcommands with their argument synopsis and description
+const SUBCOMMANDS: &[(&str, &str, &str)] = &[
+    ("run", "", "Stream a payload per poll and on desktop events (default)"),
+    ("once", "", "Print a single payload and exit"),
+    ("list-collectors", "", "List collectors with their payload key and options"),
+    ("check-config", "", "Validate the config file and exit"),
+    ("completions", "fish", "Print shell completions"),
+    ("layout", "[next|prev|<index>]", "Switch the keyboard layout"),
+    ("workspace", "<number>", "Focus a workspace"),
+    ("profile", "<name|next>", "Switch power profile, governor and EPP"),
+    ("thresholds", "<start> <end>", "Set battery charge thresholds"),
+    ("help", "", "Show this help"),
+];
+
+/// Subcommands taking the long options below
+const OPTION_SUBCOMMANDS: &[&str] = &["run", "once", "check-config"];
+/// Subcommands writing to sysfs, which only take the root options
+const ROOT_SUBCOMMANDS: &[&str] = &["profile", "thresholds"];
+/// Options choosing where procfs and sysfs are read from
+const ROOT_OPTIONS: &[&str] = &["proc-root", "sys-root"];
+
+/// Long options with their value name and description
+const OPTIONS: &[(&str, &str, &str)] = &[
+    ("interval", "MS", "Sampling interval in milliseconds of every collector without its own interval_ms"),
+    ("format", "FORMAT", "Output format: json or pretty"),
+    ("collectors", "LIST", "Comma-separated collectors to enable, others are disabled"),
+    ("title-width", "N", "Maximum window title length, 0 disables truncation"),
+    ("adaptive", "on|off", "Poll slower on battery, when idle or when the reader is blocked"),
+    ("config", "PATH", "Config file instead of ~/.config/eww/polling-server.toml"),
+    ("proc-root", "DIR", "Read procfs from DIR instead of /proc (e.g., /host/proc)"),
+    ("sys-root", "DIR", "Read sysfs from DIR instead of /sys (e.g., /host/sys)"),
+];
+
+/// Invalid command line
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (see --help)", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Options overriding the config file
+#[derive(Default)]
+pub struct Options {
+    pub interval: Option<Duration>,
+    pub format: Option<Format>,
+    /// Collectors to enable; `None` keeps the config's choice
+    pub collectors: Option<Vec<String>>,
+    pub title_width: Option<usize>,
+    pub adaptive: Option<bool>,
+    pub config: Option<PathBuf>,
+    pub proc_root: Option<PathBuf>,
+    pub sys_root: Option<PathBuf>,
+}
+
+impl Options {
+    /// Where the collectors read procfs and sysfs.
+    pub fn roots(&self) -> Roots {
+        let defaults = Roots::default();
+        Roots {
+            proc: self.proc_root.clone().unwrap_or(defaults.proc),
+            sys: self.sys_root.clone().unwrap_or(defaults.sys),
+        }
+    }
+
+    /// Apply the overrides to `config`, checking collector names against `registry`.
+    pub fn apply(&self, config: &mut Config, registry: &Registry) -> Result<(), Error> {
+        if let Some(interval) = self.interval {
+            config.interval = Some(interval);
+        }
+        if let Some(format) = self.format {
+            config.format = format;
+        }
+        if let Some(width) = self.title_width {
+            config.title_width = width;
+        }
+        if let Some(adaptive) = self.adaptive {
+            config.adaptive = adaptive;
+        }
+        if let Some(enabled) = &self.collectors {
+            let names: Vec<&str> = registry.names().collect();
+            if let Some(unknown) = enabled.iter().find(|name| !names.contains(&name.as_str())) {
+                return Err(Error(format!(
+                    "--collectors: unknown collector \"{}\", expected some of: {}",
+                    unknown,
+                    names.join(", ")
+                )));
+            }
+            for name in names {
+                config.collector_mut(name).enabled = enabled.iter().any(|n| n == name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parsed command line
+pub enum Command {
+    Run(Options),
+    Once(Options),
+    ListCollectors,
+    CheckConfig(Options),
+    Completions(String),
+    Layout(Option<String>),
+    Workspace(Option<String>),
+    /// Target, with only `proc_root` and `sys_root` set in the options
+    Profile(Option<String>, Options),
+    /// Start and end, with only `proc_root` and `sys_root` set in the options
+    Thresholds(Option<String>, Option<String>, Options),
+    Help,
+}
+
+/// Parse the arguments after the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, Error> {
+    let args: Vec<String> = args.into_iter().collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(Command::Help);
+    }
+    let mut args = args.into_iter().peekable();
+    let subcommand = match args.peek().map(String::as_str) {
+        None => "run".to_string(),
+        Some(arg) if arg.starts_with('-') => "run".to_string(),
+        Some(_) => args.next().unwrap_or_default(),
+    };
+    let mut rest = args;
+
+    match subcommand.as_str() {
+        "run" => Ok(Command::Run(parse_options(&mut rest, false)?)),
+        "once" => Ok(Command::Once(parse_options(&mut rest, false)?)),
+        "check-config" => Ok(Command::CheckConfig(parse_options(&mut rest, false)?)),
+        "list-collectors" => no_more(rest, Command::ListCollectors),
+        "completions" => match rest.next() {
+            Some(shell) if shell == "fish" => no_more(rest, Command::Completions(shell)),
+            Some(shell) => Err(Error(format!("completions: unsupported shell \"{}\", expected fish", shell))),
+            None => Err(Error("completions: expected a shell name (fish)".to_string())),
+        },
+        "layout" => {
+            let target = positional(&mut rest);
+            no_more(rest, Command::Layout(target))
+        }
+        "workspace" => {
+            let number = positional(&mut rest);
+            no_more(rest, Command::Workspace(number))
+        }
+        "profile" => {
+            let target = positional(&mut rest);
+            Ok(Command::Profile(target, parse_options(&mut rest, true)?))
+        }
+        "thresholds" => {
+            let start = positional(&mut rest);
+            let end = positional(&mut rest);
+            Ok(Command::Thresholds(start, end, parse_options(&mut rest, true)?))
+        }
+        "help" => Ok(Command::Help),
+        other => Err(Error(format!("unknown command \"{}\"", other))),
+    }
+}
+
+fn no_more(mut rest: impl Iterator<Item = String>, command: Command) -> Result<Command, Error> {
+    match rest.next() {
+        Some(arg) => Err(Error(format!("unexpected argument \"{}\"", arg))),
+        None => Ok(command),
+    }
+}
+
+/// Next argument unless it is an option.
+fn positional(rest: &mut Peekable<impl Iterator<Item = String>>) -> Option<String> {
+    rest.next_if(|arg| !arg.starts_with("--"))
+}
+
+/// Parse `--name value` and `--name=value` options, only `ROOT_OPTIONS` if `roots_only`.
+fn parse_options(args: &mut impl Iterator<Item = String>, roots_only: bool) -> Result<Options, Error> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let Some(long) = arg.strip_prefix("--") else {
+            return Err(Error(format!("unexpected argument \"{}\"", arg)));
+        };
+        let (name, inline) = match long.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (long, None),
+        };
+        if !OPTIONS.iter().any(|(option, _, _)| *option == name) {
+            return Err(Error(format!("unknown option \"--{}\"", name)));
+        }
+        if roots_only && !ROOT_OPTIONS.contains(&name) {
+            return Err(Error(format!("unexpected argument \"{}\"", arg)));
+        }
+        let value = inline
+            .or_else(|| args.next())
+            .ok_or_else(|| Error(format!("--{}: missing value", name)))?;
+        let invalid = |expected: &str| Error(format!("--{}: invalid value \"{}\", expected {}", name, value, expected));
+
+        match name {
+            "interval" => {
+                let millis = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&ms| ms > 0)
+                    .ok_or_else(|| invalid("a positive number of milliseconds"))?;
+                options.interval = Some(Duration::from_millis(millis));
+            }
+            "format" => {
+                let format = Format::from_name(&value)
+                    .ok_or_else(|| invalid(&format!("one of: {}", Format::NAMES.join(", "))))?;
+                options.format = Some(format);
+            }
+            "collectors" => {
+                let names = value.split(',').map(str::trim).filter(|name| !name.is_empty());
+                options.collectors = Some(names.map(str::to_string).collect());
+            }
+            "title-width" => {
+                options.title_width = Some(value.parse::<usize>().map_err(|_| invalid("a number of characters"))?);
+            }
+            "adaptive" => {
+                options.adaptive = Some(match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid("on or off")),
+                });
+            }
+            "proc-root" => options.proc_root = Some(PathBuf::from(value)),
+            "sys-root" => options.sys_root = Some(PathBuf::from(value)),
+            _ => options.config = Some(PathBuf::from(value)),
+        }
+    }
+    Ok(options)
+}
+
+/// Help text for `--help`.
+pub fn help() -> String {
+    let mut out = String::from(
+        "polling-server: system metrics and desktop state for the eww bar\n\n\
+         Usage: polling-server [COMMAND] [OPTIONS]\n\nCommands:\n",
+    );
+    for (name, args, description) in SUBCOMMANDS {
+        let usage = if args.is_empty() { name.to_string() } else { format!("{} {}", name, args) };
+        out.push_str(&format!("  {:<28}{}\n", usage, description));
+    }
+    out.push_str(&format!("\nOptions ({}):\n", OPTION_SUBCOMMANDS.join(", ")));
+    for (name, value, description) in OPTIONS {
+        let mut description = description.to_string();
+        if ROOT_OPTIONS.contains(name) {
+            description.push_str(&format!(", also for {}", ROOT_SUBCOMMANDS.join(" and ")));
+        }
+        out.push_str(&format!("  {:<28}{}\n", format!("--{} <{}>", name, value), description));
+    }
+    out.push_str("  -h, --help                  Show this help\n");
+    out
+}
+
+/// Fish completion script for `completions fish`.
+pub fn fish_completions(registry: &Registry) -> String {
+    let mut out = String::from(
+        "# polling-server completions, generated by `polling-server completions fish`\n\
+         complete -c polling-server -f\n",
+    );
+    let all: Vec<&str> = SUBCOMMANDS.iter().map(|(name, _, _)| *name).collect();
+    out.push_str(&format!("set -l commands {}\n", all.join(" ")));
+    let none_seen = "not __fish_seen_subcommand_from $commands";
+    for (name, _, description) in SUBCOMMANDS {
+        out.push_str(&format!(
+            "complete -c polling-server -n \"{}\" -a {} -d '{}'\n",
+            none_seen, name, description
+        ));
+    }
+
+    let option_condition = format!(
+        "{}; or __fish_seen_subcommand_from {}",
+        none_seen,
+        OPTION_SUBCOMMANDS.join(" ")
+    );
+    let root_condition = format!("{} {}", option_condition, ROOT_SUBCOMMANDS.join(" "));
+    let collectors: Vec<&str> = registry.names().collect();
+    for (name, _, description) in OPTIONS {
+        let values = match *name {
+            "format" => format!(" -x -a '{}'", Format::NAMES.join(" ")),
+            "collectors" => format!(" -x -a '{}'", collectors.join(" ")),
+            "adaptive" => " -x -a 'on off'".to_string(),
+            "config" => " -r -F".to_string(),
+            "proc-root" | "sys-root" => " -x -a '(__fish_complete_directories)'".to_string(),
+            _ => " -x".to_string(),
+        };
+        out.push_str(&format!(
+            "complete -c polling-server -n \"{}\" -l {}{} -d '{}'\n",
+            if ROOT_OPTIONS.contains(name) { &root_condition } else { &option_condition },
+            name,
+            values,
+            description
+        ));
+    }
+    out.push_str("complete -c polling-server -s h -l help -d 'Show help'\n");
+
+    let arguments: &[(&str, &str)] = &[
+        ("completions", "fish"),
+        ("layout", "next prev"),
+        ("profile", "next low-power balanced performance"),
+    ];
+    for (subcommand, values) in arguments {
+        out.push_str(&format!(
+            "complete -c polling-server -n '__fish_seen_subcommand_from {}' -a '{}'\n",
+            subcommand, values
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, Error> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults_to_run_and_parses_options() {
+        let Ok(Command::Run(options)) = parse_args(&["--interval=500", "--format", "pretty"]) else {
+            panic!("expected run");
+        };
+        assert_eq!(options.interval, Some(Duration::from_millis(500)));
+        assert_eq!(options.format, Some(Format::Pretty));
+        assert!(matches!(parse_args(&[]), Ok(Command::Run(_))));
+
+        let Ok(Command::Once(options)) = parse_args(&["once", "--proc-root", "/host/proc"]) else {
+            panic!("expected once");
+        };
+        let roots = options.roots();
+        assert_eq!(roots.resolve("/proc/net/dev"), PathBuf::from("/host/proc/net/dev"));
+        assert_eq!(roots.resolve("/sys/class/net"), PathBuf::from("/sys/class/net"));
+        assert!(matches!(parse_args(&["once", "--help"]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let err = parse_args(&["run", "--interval", "30OO"]).err().unwrap();
+        assert_eq!(err.0, "--interval: invalid value \"30OO\", expected a positive number of milliseconds");
+        assert!(parse_args(&["--interval", "0"]).is_err());
+        assert!(parse_args(&["--interval"]).is_err());
+        assert!(parse_args(&["3000"]).is_err());
+        assert!(parse_args(&["once", "--verbose"]).is_err());
+    }
+
+    #[test]
+    fn rejects_trailing_arguments() {
+        let err = parse_args(&["workspace", "3", "foo"]).err().unwrap();
+        assert_eq!(err.0, "unexpected argument \"foo\"");
+        assert!(parse_args(&["layout", "next", "prev"]).is_err());
+        assert!(parse_args(&["thresholds", "75", "80", "90"]).is_err());
+        assert!(parse_args(&["profile", "next", "--interval", "500"]).is_err());
+
+        let Ok(Command::Thresholds(start, end, options)) = parse_args(&["thresholds", "75", "80", "--sys-root=/tmp/sys"])
+        else {
+            panic!("expected thresholds");
+        };
+        assert_eq!((start.as_deref(), end.as_deref()), (Some("75"), Some("80")));
+        assert_eq!(options.roots().resolve("/sys/class"), PathBuf::from("/tmp/sys/class"));
+        assert!(matches!(parse_args(&["profile", "--sys-root", "/tmp/sys"]), Ok(Command::Profile(None, _))));
+    }
+}
@@ -0,0 +1,460 @@
+//! The `Collector` trait and the registry driving all payload sections.
+//!
+//! A collector owns one section of the payload: it opens its files once in
+//! `init`, takes a sample every poll and appends the section value as JSON.
+//! In-house collectors implement the trait and are added with `Registry::register`.
+
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::battery::BatteryCollector;
+use crate::config::{CollectorConfig, Config, DEFAULT_INTERVAL};
+use crate::cpu::CpuCollector;
+use crate::devices::DeviceEvent;
+use crate::disk::DiskCollector;
+use crate::memory::MemoryCollector;
+use crate::network::NetworkCollector;
+use crate::profile::ProfileCollector;
+use crate::rapl::PowerCollector;
+use crate::source::{Roots, REOPEN_MAX, REOPEN_MIN};
+use crate::suspend::SuspendCollector;
+use crate::throttle::ThrottleCollector;
+use crate::push_json_str;
+
+/// What a collector's last sample says about how busy the machine is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Activity {
+    /// The collector doesn't tell, or has no sample yet
+    Unknown,
+    Idle,
+    Busy,
+}
+
+/// One section of the payload
+pub trait Collector {
+    /// Name of the collector in the config and on the command line (e.g., "cpu")
+    fn name(&self) -> &'static str;
+
+    /// Payload key of the section (e.g., "c")
+    fn key(&self) -> &'static str;
+
+    /// Sampling period unless configured otherwise: the collector's own `interval_ms`
+    /// wins, then the global interval from `--interval` or the config.
+    fn interval(&self) -> Duration {
+        DEFAULT_INTERVAL
+    }
+
+    /// Kernel uevent subsystem (e.g., "power_supply") whose events trigger an
+    /// immediate sample on top of the periodic ones.
+    fn uevent_subsystem(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Options accepted in its `[collectors.<name>]` table besides `enabled` and `interval_ms`.
+    fn options(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Apply its validated `[collectors.<name>]` settings; called before `init`
+    /// and again when the config is reloaded.
+    fn configure(&mut self, _config: &CollectorConfig) {}
+
+    /// Open files and discover devices under `roots`; called once before the first sample.
+    /// An error disables the collector and is reported under "e".
+    fn init(&mut self, _roots: &Roots) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Take a sample. `elapsed` is the time since the previous sample in seconds.
+    fn sample(&mut self, elapsed: f64);
+
+    /// Append the section value as JSON.
+    fn serialize(&self, out: &mut String);
+
+    /// Error to report under "e" while the collector's source is failing.
+    fn error(&self) -> Option<&str> {
+        None
+    }
+
+    /// Forget previous counters so the next sample is a baseline (e.g., after resume).
+    fn reset_baselines(&mut self) {}
+
+    /// Devices that appeared or vanished since the events were last cleared, reported
+    /// under "v". They add up over samples until a payload carrying them is written.
+    fn device_events(&self) -> &[DeviceEvent] {
+        &[]
+    }
+
+    /// Drop device events once a payload carrying them has been written.
+    fn clear_device_events(&mut self) {}
+
+    /// Whether the last sample saw the machine idle or busy, for adaptive polling.
+    fn activity(&self) -> Activity {
+        Activity::Unknown
+    }
+
+    /// Whether the last sample saw the machine running on battery.
+    fn on_battery(&self) -> bool {
+        false
+    }
+}
+
+struct Registered {
+    collector: Box<dyn Collector>,
+    /// Set once `init` succeeded; the collector is not sampled before
+    initialized: bool,
+    /// Error of the last failed `init`, reported until a retry succeeds
+    init_error: Option<String>,
+    /// Earliest time of the next `init` retry
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    interval: Duration,
+    /// Time of the previous sample, `None` before the first one
+    last_sample: Option<Instant>,
+    /// Scheduled time of the next periodic sample, `None` until the first one
+    due: Option<Instant>,
+}
+
+impl Registered {
+    /// Run the collector's `init`, scheduling a retry with the same backoff as
+    /// a failing `ProcSource`. Returns whether it succeeded.
+    fn try_init(&mut self, roots: &Roots, now: Instant) -> bool {
+        match self.collector.init(roots) {
+            Ok(()) => {
+                self.initialized = true;
+                self.init_error = None;
+                self.retry_at = None;
+                self.backoff = REOPEN_MIN;
+                true
+            }
+            Err(err) => {
+                self.init_error = Some(err.to_string());
+                self.retry_at = Some(now + self.backoff);
+                self.backoff = (self.backoff * 2).min(REOPEN_MAX);
+                false
+            }
+        }
+    }
+}
+
+/// Collectors due this close together are sampled in the same wakeup
+const SAMPLE_SLACK: Duration = Duration::from_millis(10);
+
+/// Next tick after `now` on a grid of `interval` aligned to the wall clock, so
+/// e.g. 1 s collectors tick with the clock's seconds. Ticks closer than half an
+/// interval are skipped, keeping the first rate sample from being too short.
+fn aligned(now: Instant, interval: Duration) -> Instant {
+    let period = interval.as_nanos();
+    if period == 0 {
+        return now;
+    }
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut ahead = period - since_epoch.as_nanos() % period;
+    if ahead < period / 2 {
+        ahead += period;
+    }
+    now + Duration::from_nanos(ahead as u64)
+}
+
+/// Collectors in payload order, each sampled on its own schedule
+pub struct Registry {
+    collectors: Vec<Registered>,
+    /// Every interval is multiplied by this; 1 outside adaptive polling
+    slowdown: u32,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry {
+            collectors: Vec::new(),
+            slowdown: 1,
+        }
+    }
+}
+
+impl Registry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Registry with every built-in collector.
+    pub fn with_defaults() -> Self {
+        let mut registry = Registry::new();
+        registry.register(Box::new(CpuCollector::default()));
+        registry.register(Box::new(MemoryCollector::default()));
+        registry.register(Box::new(NetworkCollector::default()));
+        registry.register(Box::new(DiskCollector::default()));
+        registry.register(Box::new(PowerCollector::default()));
+        registry.register(Box::new(ThrottleCollector::default()));
+        registry.register(Box::new(ProfileCollector::default()));
+        registry.register(Box::new(BatteryCollector::default()));
+        registry.register(Box::new(SuspendCollector::default()));
+        registry
+    }
+
+    /// Add a collector; its section follows those registered before it.
+    pub fn register(&mut self, collector: Box<dyn Collector>) {
+        self.collectors.push(Registered {
+            collector,
+            initialized: false,
+            init_error: None,
+            retry_at: None,
+            backoff: REOPEN_MIN,
+            interval: DEFAULT_INTERVAL,
+            last_sample: None,
+            due: None,
+        });
+    }
+
+    /// Names of the registered collectors, in payload order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.collectors.iter().map(|entry| entry.collector.name())
+    }
+
+    /// Extra config options of the collector called `name`, `None` if there is none.
+    pub fn options(&self, name: &str) -> Option<&'static [&'static str]> {
+        self.collectors
+            .iter()
+            .find(|entry| entry.collector.name() == name)
+            .map(|entry| entry.collector.options())
+    }
+
+    /// Drop disabled collectors and hand the others their settings and interval.
+    pub fn configure(&mut self, config: &Config) {
+        let default = CollectorConfig::default();
+        self.collectors.retain_mut(|entry| {
+            let settings = config.collector(entry.collector.name()).unwrap_or(&default);
+            if !settings.enabled {
+                return false;
+            }
+            // A collector's own period (e.g., CPU every second) only applies
+            // while no interval is configured for it or globally
+            entry.interval = settings
+                .interval
+                .or(config.interval)
+                .unwrap_or_else(|| entry.collector.interval());
+            entry.collector.configure(settings);
+            true
+        });
+    }
+
+    /// Payload keys of the registered collectors, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.collectors.iter().map(|entry| entry.collector.key())
+    }
+
+    /// Switch to the collectors of `next`, a registry built from scratch, configured
+    /// for `config`. Collectors already running are kept with their baselines
+    /// and only reconfigured, their schedule moved to the new interval; new ones
+    /// still need `init`.
+    pub fn reconfigure(&mut self, next: Registry, config: &Config) {
+        let mut previous = std::mem::take(&mut self.collectors);
+        for entry in next.collectors {
+            let name = entry.collector.name();
+            match previous.iter().position(|old| old.collector.name() == name) {
+                Some(pos) => self.collectors.push(previous.swap_remove(pos)),
+                None => self.collectors.push(entry),
+            }
+        }
+        self.configure(config);
+        let now = Instant::now();
+        for entry in self.collectors.iter_mut().filter(|entry| entry.due.is_some()) {
+            entry.due = Some(aligned(now, entry.interval * self.slowdown));
+        }
+    }
+
+    /// Initialize every collector not initialized yet, reading from `roots`.
+    /// A collector that fails is retried by `retry_init` with backoff.
+    pub fn init(&mut self, roots: &Roots) {
+        let now = Instant::now();
+        for entry in self.collectors.iter_mut().filter(|entry| !entry.initialized) {
+            entry.try_init(roots, now);
+        }
+    }
+
+    /// Retry the failed `init`s whose backoff has passed at `now`, e.g. sysfs not
+    /// populated yet at startup. Returns whether any collector came up.
+    pub fn retry_init(&mut self, roots: &Roots, now: Instant) -> bool {
+        let mut recovered = false;
+        for entry in self.collectors.iter_mut() {
+            if entry.retry_at.is_some_and(|retry_at| retry_at <= now + SAMPLE_SLACK) {
+                recovered |= entry.try_init(roots, now);
+            }
+        }
+        recovered
+    }
+
+    /// Sample every initialized collector that is due at `now`.
+    /// The others keep their previous section.
+    #[inline]
+    pub fn sample(&mut self, now: Instant) {
+        self.sample_where(now, |entry| entry.due.is_none_or(|due| due <= now + SAMPLE_SLACK));
+    }
+
+    /// Sample every initialized collector regardless of its schedule.
+    pub fn sample_all(&mut self, now: Instant) {
+        self.sample_where(now, |_| true);
+    }
+
+    /// Sample the collectors listening for uevents of `subsystem`, returning
+    /// whether there was any.
+    pub fn sample_uevent(&mut self, subsystem: &str, now: Instant) -> bool {
+        let mut matched = false;
+        self.sample_where(now, |entry| {
+            let listens = entry.collector.uevent_subsystem() == Some(subsystem);
+            matched |= listens;
+            listens
+        });
+        matched
+    }
+
+    /// Kernel uevent subsystems the collectors listen for, without duplicates.
+    pub fn uevent_subsystems(&self) -> Vec<&'static str> {
+        let mut subsystems: Vec<&'static str> = Vec::new();
+        for entry in self.collectors.iter().filter(|entry| entry.initialized) {
+            if let Some(subsystem) = entry.collector.uevent_subsystem() {
+                if !subsystems.contains(&subsystem) {
+                    subsystems.push(subsystem);
+                }
+            }
+        }
+        subsystems
+    }
+
+    /// Whether any collector saw the machine busy, else whether any saw it idle.
+    pub fn activity(&self) -> Activity {
+        let mut activity = Activity::Unknown;
+        for entry in self.collectors.iter().filter(|entry| entry.initialized) {
+            match entry.collector.activity() {
+                Activity::Busy => return Activity::Busy,
+                Activity::Idle => activity = Activity::Idle,
+                Activity::Unknown => {}
+            }
+        }
+        activity
+    }
+
+    /// Whether any collector saw the machine running on battery.
+    pub fn on_battery(&self) -> bool {
+        self.collectors
+            .iter()
+            .any(|entry| entry.initialized && entry.collector.on_battery())
+    }
+
+    /// Stretch every interval by `factor`, 1 for the configured rates. Collectors
+    /// due later than the new interval allows are pulled in, so speeding up takes
+    /// effect at once. Returns whether the factor changed.
+    pub fn set_slowdown(&mut self, factor: u32, now: Instant) -> bool {
+        let factor = factor.max(1);
+        if factor == self.slowdown {
+            return false;
+        }
+        self.slowdown = factor;
+        for entry in self.collectors.iter_mut() {
+            if let (Some(last), Some(due)) = (entry.last_sample, entry.due) {
+                let sooner = last + entry.interval * factor;
+                if sooner < due {
+                    entry.due = Some(sooner.max(now));
+                }
+            }
+        }
+        true
+    }
+
+    #[inline]
+    fn sample_where(&mut self, now: Instant, mut selected: impl FnMut(&Registered) -> bool) {
+        let slowdown = self.slowdown;
+        for entry in self.collectors.iter_mut().filter(|entry| entry.initialized) {
+            if !selected(entry) {
+                continue;
+            }
+            let elapsed = entry.last_sample.map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+            entry.collector.sample(elapsed);
+            entry.last_sample = Some(now);
+
+            // Periodic samples stay on their grid; out-of-band samples (uevents, `once`)
+            // don't move it, and a schedule left behind by a stall is aligned again
+            let interval = entry.interval * slowdown;
+            match entry.due {
+                None => entry.due = Some(aligned(now, interval)),
+                Some(due) if due <= now + SAMPLE_SLACK => {
+                    let next = due + interval;
+                    entry.due = Some(if next <= now { aligned(now, interval) } else { next });
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// When the next collector is due or the next failed `init` is retried,
+    /// `None` if there is neither.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.collectors
+            .iter()
+            .filter_map(|entry| match entry.initialized {
+                true => Some(entry.due.unwrap_or_else(Instant::now)),
+                false => entry.retry_at,
+            })
+            .min()
+    }
+
+    /// Make the next sample of every collector a baseline.
+    pub fn reset_baselines(&mut self) {
+        for entry in self.collectors.iter_mut() {
+            entry.collector.reset_baselines();
+        }
+    }
+
+    /// Drop device events after a payload carrying them has been written, so later
+    /// payloads don't repeat them. Returns whether there were any.
+    #[inline]
+    pub fn clear_device_events(&mut self) -> bool {
+        let mut cleared = false;
+        for entry in self.collectors.iter_mut() {
+            cleared |= !entry.collector.device_events().is_empty();
+            entry.collector.clear_device_events();
+        }
+        cleared
+    }
+
+    /// Append `"key":value` for every section, then device events ("v") and errors ("e").
+    #[inline]
+    pub fn serialize(&self, out: &mut String) {
+        for entry in &self.collectors {
+            out.push('"');
+            out.push_str(entry.collector.key());
+            out.push_str("\":");
+            entry.collector.serialize(out);
+            out.push(',');
+        }
+
+        out.push_str("\"v\":[");
+        let events = self.collectors.iter().flat_map(|entry| entry.collector.device_events());
+        for (idx, event) in events.enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str(if event.added { "[\"+\",\"" } else { "[\"-\",\"" });
+            out.push_str(event.class.as_str());
+            out.push_str("\",");
+            push_json_str(out, &event.name);
+            out.push(']');
+        }
+
+        out.push_str("],\"e\":{");
+        let errors = self.collectors.iter().filter_map(|entry| {
+            let error = entry.init_error.as_deref().or_else(|| entry.collector.error())?;
+            Some((entry.collector.key(), error))
+        });
+        for (idx, (key, error)) in errors.enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            push_json_str(out, key);
+            out.push(':');
+            push_json_str(out, error);
+        }
+        out.push('}');
+    }
+}
@@ -0,0 +1,173 @@
+//! Compositor-neutral state fed by the sway and Hyprland IPC backends.
+//!
+//! Both backends emit the same entries, so the payload shape does not depend
+//! on the session the bar runs in.
+
+use std::io;
+use std::time::Duration;
+
+use crate::event_loop::EventSource;
+use crate::{hyprland, sway};
+
+/// Longest wait for an IPC reply; the event loop is blocked meanwhile
+pub const IPC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Active keyboard layout for output
+pub struct LayoutEntry {
+    /// xkb layout name (e.g., "English (US)"), lowercased in the payload
+    pub name: String,
+    /// Index into the configured layout list
+    pub index: u32,
+}
+
+/// Workspace entry for output
+pub struct WorkspaceEntry {
+    /// Workspace number (sway `num`, Hyprland `id`); -1 for named sway workspaces
+    pub id: i64,
+    /// Display name
+    pub name: String,
+    /// Whether the workspace has keyboard focus
+    pub focused: bool,
+    /// Whether a window on it requested attention
+    pub urgent: bool,
+}
+
+/// Focused window entry for output
+pub struct WindowEntry {
+    /// Window title
+    pub title: String,
+    /// Wayland app_id, or X11/Hyprland class
+    pub app_id: String,
+    /// Whether the window floats above the tiling layout
+    pub floating: bool,
+    /// Whether the window is fullscreen (on its output or globally)
+    pub fullscreen: bool,
+}
+
+/// Latest compositor-derived data, updated from backend events
+#[derive(Default)]
+pub struct CompositorState {
+    pub workspaces: Vec<WorkspaceEntry>,
+    pub window: Option<WindowEntry>,
+    pub layout: Option<LayoutEntry>,
+    /// Binding mode (sway) or submap (Hyprland); `None` in the default one
+    pub mode: Option<String>,
+}
+
+/// Shorten `text` to at most `width` characters, ending with an ellipsis when cut.
+/// Counts and cuts on char boundaries, so multi-byte titles never split a code point.
+/// A width of 0 disables truncation.
+pub fn truncate_chars(text: &mut String, width: usize) {
+    if width == 0 {
+        return;
+    }
+    // Only cut when there is a char past the limit
+    if text.char_indices().nth(width).is_none() {
+        return;
+    }
+    let keep = text.char_indices().nth(width - 1).map_or(0, |(pos, _)| pos);
+    text.truncate(keep);
+    text.push('…');
+}
+
+/// IPC backend of the running session
+pub enum Backend {
+    /// sway, with the `$SWAYSOCK` path
+    Sway(String),
+    /// Hyprland, with the instance socket directory
+    Hyprland(String),
+}
+
+impl Backend {
+    /// Pick the backend from the session environment. Hyprland wins when both
+    /// are set, since `SWAYSOCK` can leak into a nested Hyprland session.
+    pub fn detect() -> Option<Backend> {
+        if let Some(dir) = hyprland::socket_dir() {
+            return Some(Backend::Hyprland(dir));
+        }
+        sway::socket_path().map(Backend::Sway)
+    }
+
+    /// Name used when reporting errors
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Sway(_) => "sway ipc",
+            Backend::Hyprland(_) => "hyprland ipc",
+        }
+    }
+
+    /// Connect to the backend's event stream, to be added to the event loop.
+    pub fn connect(&self) -> io::Result<Box<dyn EventSource>> {
+        Ok(match self {
+            Backend::Sway(path) => Box::new(sway::SwaySource::connect(path)?),
+            Backend::Hyprland(dir) => Box::new(hyprland::HyprlandSource::connect(dir)?),
+        })
+    }
+
+    /// Switch every keyboard to another layout (`next`, `prev` or an index).
+    pub fn switch_layout(&self, target: &str) -> io::Result<()> {
+        match self {
+            Backend::Sway(path) => sway::switch_layout(path, target),
+            Backend::Hyprland(dir) => hyprland::switch_layout(dir, target),
+        }
+    }
+
+    /// Focus a workspace by number.
+    pub fn focus_workspace(&self, id: i64) -> io::Result<()> {
+        match self {
+            Backend::Sway(path) => sway::focus_workspace(path, id),
+            Backend::Hyprland(dir) => hyprland::focus_workspace(dir, id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn truncated(text: &str, width: usize) -> String {
+        let mut text = text.to_string();
+        truncate_chars(&mut text, width);
+        text
+    }
+
+    #[test]
+    fn ascii_titles() {
+        assert_eq!(truncated("Terminal", 4), "Ter\u{2026}");
+        assert_eq!(truncated("Terminal", 100), "Terminal");
+        assert_eq!(truncated("", 3), "");
+    }
+
+    #[test]
+    fn width_at_the_limit() {
+        // Exactly `width` chars fit; one more is cut
+        assert_eq!(truncated("abcde", 5), "abcde");
+        assert_eq!(truncated("abcdef", 5), "abcd\u{2026}");
+        assert_eq!(truncated("d\u{e9}j\u{e0} vu", 7), "d\u{e9}j\u{e0} vu");
+    }
+
+    #[test]
+    fn width_zero_and_one() {
+        assert_eq!(truncated("Terminal", 0), "Terminal");
+        assert_eq!(truncated("T", 1), "T");
+        assert_eq!(truncated("Terminal", 1), "\u{2026}");
+    }
+
+    #[test]
+    fn multi_byte_titles() {
+        // Two-byte Latin, three-byte CJK: counted in chars, never cut inside one
+        assert_eq!(truncated("\u{e9}t\u{e9} \u{e0} Paris", 4), "\u{e9}t\u{e9}\u{2026}");
+        assert_eq!(truncated("\u{65e5}\u{672c}\u{8a9e}\u{306e}\u{30bf}\u{30a4}\u{30c8}\u{30eb}", 3), "\u{65e5}\u{672c}\u{2026}");
+    }
+
+    #[test]
+    fn emoji_titles() {
+        // Four-byte emoji count as one char each
+        assert_eq!(truncated("\u{1f600}\u{1f600}\u{1f600} fun", 3), "\u{1f600}\u{1f600}\u{2026}");
+        assert_eq!(truncated("\u{1f600}\u{1f600}\u{1f600}", 3), "\u{1f600}\u{1f600}\u{1f600}");
+        // Clusters are not kept together: a ZWJ sequence is several chars
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(truncated(family, 5), family);
+        assert_eq!(truncated(family, 2), "\u{1f468}\u{2026}");
+    }
+}
@@ -0,0 +1,650 @@
+//! `$XDG_CONFIG_HOME/eww/polling-server.toml`: collectors, intervals, filters and output format.
+//!
+//! Only the TOML subset the config needs is parsed: tables, bare and dotted
+//! keys, strings, integers, floats, booleans and (multi-line) arrays. Errors
+//! carry the line and the full dotted key so a typo is easy to find.
+//!
+//! ```toml
+//! interval_ms = 2000
+//! format = "json"
+//! adaptive = true
+//!
+//! [collectors.network]
+//! ref_bps = 1_250_000_000.0
+//! exclude = ["tun*", "wg*"]
+//!
+//! [collectors.suspend]
+//! enabled = false
+//! ```
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::output::Format;
+use crate::Registry;
+
+/// Sampling period of collectors that don't pick their own
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(3000);
+/// Default focused-window title width in characters
+pub const DEFAULT_TITLE_WIDTH: usize = 60;
+
+/// Options every collector accepts
+const COMMON_OPTIONS: &[&str] = &["enabled", "interval_ms"];
+
+/// Parsed TOML value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+/// `key = value` with its full dotted key (table prefix included)
+struct Entry {
+    key: String,
+    value: Value,
+    line: usize,
+}
+
+/// Config error pointing at the offending line and key
+#[derive(Debug)]
+pub struct Error {
+    pub path: Option<PathBuf>,
+    /// 1-based line, 0 when the error is not tied to a line
+    pub line: usize,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl Error {
+    fn at(line: usize, key: Option<&str>, message: impl Into<String>) -> Self {
+        Error {
+            path: None,
+            line,
+            key: key.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if self.line > 0 {
+            write!(f, "{}:", self.line)?;
+        }
+        if self.path.is_some() || self.line > 0 {
+            f.write_str(" ")?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+/// Settings of one collector (`[collectors.<name>]`)
+#[derive(Debug, Clone)]
+pub struct CollectorConfig {
+    pub enabled: bool,
+    /// Sampling interval; unset, the global one or else the collector's own period
+    pub interval: Option<Duration>,
+    /// Reference bandwidth in bytes/s for the 0-10 levels (network, disk)
+    pub ref_bps: Option<f64>,
+    /// Device name globs to keep; empty keeps all (network, disk)
+    pub include: Vec<String>,
+    /// Device name globs to drop (network, disk)
+    pub exclude: Vec<String>,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        CollectorConfig {
+            enabled: true,
+            interval: None,
+            ref_bps: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+/// Validated configuration
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Sampling interval for collectors without an `interval_ms`, overriding their
+    /// own periods (e.g., CPU every second); unset, those or `DEFAULT_INTERVAL`
+    pub interval: Option<Duration>,
+    /// Maximum focused-window title length in characters, 0 disables truncation
+    pub title_width: usize,
+    pub format: Format,
+    /// Lengthen the intervals on battery, while idle or while the reader is blocked
+    pub adaptive: bool,
+    /// Per-collector settings by collector name; collectors not listed use defaults
+    pub collectors: Vec<(String, CollectorConfig)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            interval: None,
+            title_width: DEFAULT_TITLE_WIDTH,
+            format: Format::Json,
+            adaptive: false,
+            collectors: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Settings of the collector called `name`.
+    pub fn collector(&self, name: &str) -> Option<&CollectorConfig> {
+        self.collectors.iter().find(|(n, _)| n == name).map(|(_, config)| config)
+    }
+
+    /// Settings of the collector called `name`, added with defaults if missing.
+    pub fn collector_mut(&mut self, name: &str) -> &mut CollectorConfig {
+        let index = match self.collectors.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+                self.collectors.push((name.to_string(), CollectorConfig::default()));
+                self.collectors.len() - 1
+            }
+        };
+        &mut self.collectors[index].1
+    }
+
+    /// Load and validate the config at `path`. A missing file yields the defaults.
+    pub fn load(path: &Path, registry: &Registry) -> Result<Config, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => {
+                return Err(Error {
+                    path: Some(path.to_path_buf()),
+                    line: 0,
+                    key: None,
+                    message: err.to_string(),
+                })
+            }
+        };
+        Config::parse(&text, registry).map_err(|mut err| {
+            err.path = Some(path.to_path_buf());
+            err
+        })
+    }
+
+    /// Parse and validate config text against the collectors in `registry`.
+    pub fn parse(text: &str, registry: &Registry) -> Result<Config, Error> {
+        let mut config = Config::default();
+        for entry in parse_document(text)? {
+            config.apply(&entry, registry)?;
+        }
+        Ok(config)
+    }
+
+    fn apply(&mut self, entry: &Entry, registry: &Registry) -> Result<(), Error> {
+        let key = entry.key.as_str();
+        let err = |message: String| Error::at(entry.line, Some(key), message);
+        match key.split_once('.') {
+            None => match key {
+                "interval_ms" => self.interval = Some(expect_interval(entry)?),
+                "title_width" => {
+                    self.title_width = usize::try_from(expect_integer(entry)?)
+                        .map_err(|_| err("must not be negative".to_string()))?
+                }
+                "format" => {
+                    let name = expect_string(entry)?;
+                    self.format = Format::from_name(name).ok_or_else(|| {
+                        err(format!("unknown format \"{}\", expected one of: {}", name, Format::NAMES.join(", ")))
+                    })?
+                }
+                "adaptive" => self.adaptive = expect_bool(entry)?,
+                _ => return Err(err("unknown key".to_string())),
+            },
+            Some(("collectors", rest)) => {
+                let Some((name, option)) = rest.split_once('.') else {
+                    return Err(err("expected a [collectors.<name>] table".to_string()));
+                };
+                let Some(options) = registry.options(name) else {
+                    let names: Vec<&str> = registry.names().collect();
+                    return Err(Error::at(
+                        entry.line,
+                        Some(key),
+                        format!("unknown collector \"{}\", expected one of: {}", name, names.join(", ")),
+                    ));
+                };
+                if !COMMON_OPTIONS.contains(&option) && !options.contains(&option) {
+                    let mut known: Vec<&str> = COMMON_OPTIONS.to_vec();
+                    known.extend_from_slice(options);
+                    return Err(err(format!("unknown option, {} accepts: {}", name, known.join(", "))));
+                }
+
+                let collector = self.collector_mut(name);
+                match option {
+                    "enabled" => collector.enabled = expect_bool(entry)?,
+                    "interval_ms" => collector.interval = Some(expect_interval(entry)?),
+                    "ref_bps" => {
+                        let bps = expect_number(entry)?;
+                        if bps <= 0.0 {
+                            return Err(err("must be greater than 0".to_string()));
+                        }
+                        collector.ref_bps = Some(bps);
+                    }
+                    "include" => collector.include = expect_strings(entry)?,
+                    "exclude" => collector.exclude = expect_strings(entry)?,
+                    _ => return Err(err("option is not supported by this version".to_string())),
+                }
+            }
+            Some(_) => return Err(err("unknown key".to_string())),
+        }
+        Ok(())
+    }
+}
+
+/// `$XDG_CONFIG_HOME/eww/polling-server.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("eww").join("polling-server.toml"))
+}
+
+fn type_error(entry: &Entry, expected: &str) -> Error {
+    Error::at(
+        entry.line,
+        Some(&entry.key),
+        format!("expected {}, found {}", expected, entry.value.type_name()),
+    )
+}
+
+fn expect_bool(entry: &Entry) -> Result<bool, Error> {
+    match entry.value {
+        Value::Boolean(value) => Ok(value),
+        _ => Err(type_error(entry, "a boolean")),
+    }
+}
+
+fn expect_integer(entry: &Entry) -> Result<i64, Error> {
+    match entry.value {
+        Value::Integer(value) => Ok(value),
+        _ => Err(type_error(entry, "an integer")),
+    }
+}
+
+fn expect_number(entry: &Entry) -> Result<f64, Error> {
+    match entry.value {
+        Value::Integer(value) => Ok(value as f64),
+        Value::Float(value) => Ok(value),
+        _ => Err(type_error(entry, "a number")),
+    }
+}
+
+fn expect_string(entry: &Entry) -> Result<&str, Error> {
+    match &entry.value {
+        Value::String(value) => Ok(value),
+        _ => Err(type_error(entry, "a string")),
+    }
+}
+
+fn expect_strings(entry: &Entry) -> Result<Vec<String>, Error> {
+    let Value::Array(items) = &entry.value else {
+        return Err(type_error(entry, "an array of strings"));
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            Value::String(s) => Ok(s.clone()),
+            other => Err(Error::at(
+                entry.line,
+                Some(&entry.key),
+                format!("expected an array of strings, found {} in it", other.type_name()),
+            )),
+        })
+        .collect()
+}
+
+/// Interval in milliseconds, at least 1.
+fn expect_interval(entry: &Entry) -> Result<Duration, Error> {
+    let millis = expect_integer(entry)?;
+    if millis < 1 {
+        return Err(Error::at(entry.line, Some(&entry.key), "must be at least 1 (milliseconds)"));
+    }
+    Ok(Duration::from_millis(millis as u64))
+}
+
+/// Parse TOML text into entries with full dotted keys.
+fn parse_document(text: &str) -> Result<Vec<Entry>, Error> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut table = String::new();
+    let mut lines = text.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+
+    while let Some((line_no, raw)) = lines.next() {
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            if header.starts_with('[') {
+                return Err(Error::at(line_no, None, "arrays of tables are not supported"));
+            }
+            let name = header
+                .strip_suffix(']')
+                .ok_or_else(|| Error::at(line_no, None, "unterminated table header"))?;
+            table = parse_key(name.trim(), line_no)?;
+            if entries.iter().any(|entry| entry.key == table) {
+                return Err(Error::at(line_no, Some(&table), "already defined as a value"));
+            }
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| Error::at(line_no, None, "expected `key = value` or a [table] header"))?;
+        let key = parse_key(key.trim(), line_no)?;
+        let key = if table.is_empty() { key } else { format!("{}.{}", table, key) };
+
+        // Arrays may span lines; keep reading until the brackets balance
+        let mut value = value.trim().to_string();
+        while value.starts_with('[') && !brackets_closed(&value) {
+            let (_, next) = lines
+                .next()
+                .ok_or_else(|| Error::at(line_no, Some(&key), "unterminated array"))?;
+            value.push(' ');
+            value.push_str(strip_comment(next).trim());
+        }
+
+        let mut parser = ValueParser { input: value.as_bytes(), pos: 0 };
+        let parsed = parser
+            .value()
+            .and_then(|v| parser.end().map(|_| v))
+            .map_err(|message| Error::at(line_no, Some(&key), message))?;
+
+        if entries.iter().any(|entry| entry.key == key) {
+            return Err(Error::at(line_no, Some(&key), "duplicate key"));
+        }
+        entries.push(Entry { key, value: parsed, line: line_no });
+    }
+    Ok(entries)
+}
+
+/// Validate a bare or dotted key ("collectors.network").
+fn parse_key(key: &str, line: usize) -> Result<String, Error> {
+    let valid_part = |part: &str| {
+        !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+    };
+    let parts: Vec<&str> = key.split('.').map(str::trim).collect();
+    if parts.iter().all(|part| valid_part(part)) {
+        Ok(parts.join("."))
+    } else {
+        Err(Error::at(line, None, format!("invalid key \"{}\"", key)))
+    }
+}
+
+/// Cut a `#` comment, ignoring `#` inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<u8> = None;
+    let mut escaped = false;
+    for (idx, b) in line.bytes().enumerate() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' && q == b'"' {
+                    escaped = true;
+                } else if b == q {
+                    quote = None;
+                }
+            }
+            None if b == b'"' || b == b'\'' => quote = Some(b),
+            None if b == b'#' => return &line[..idx],
+            None => {}
+        }
+    }
+    line
+}
+
+/// Whether every `[` outside strings is closed.
+fn brackets_closed(value: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote: Option<u8> = None;
+    let mut escaped = false;
+    for b in value.bytes() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' && q == b'"' {
+                    escaped = true;
+                } else if b == q {
+                    quote = None;
+                }
+            }
+            None => match b {
+                b'"' | b'\'' => quote = Some(b),
+                b'[' => depth += 1,
+                b']' => depth -= 1,
+                _ => {}
+            },
+        }
+    }
+    depth <= 0
+}
+
+/// Recursive-descent parser for one value
+struct ValueParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl ValueParser<'_> {
+    fn skip_ws(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_ws();
+        if self.pos < self.input.len() {
+            return Err(format!(
+                "unexpected \"{}\" after the value",
+                String::from_utf8_lossy(&self.input[self.pos..])
+            ));
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+        match self.input.get(self.pos) {
+            None => Err("missing value".to_string()),
+            Some(b'"') => self.basic_string().map(Value::String),
+            Some(b'\'') => self.literal_string().map(Value::String),
+            Some(b'[') => self.array(),
+            Some(b't') if self.input[self.pos..].starts_with(b"true") => {
+                self.pos += 4;
+                Ok(Value::Boolean(true))
+            }
+            Some(b'f') if self.input[self.pos..].starts_with(b"false") => {
+                self.pos += 5;
+                Ok(Value::Boolean(false))
+            }
+            Some(_) => self.number(),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        while let Some(&b) = self.input.get(self.pos) {
+            self.pos += 1;
+            match b {
+                b'"' => return String::from_utf8(out).map_err(|_| "invalid UTF-8 in string".to_string()),
+                b'\\' => {
+                    let escaped = self.input.get(self.pos).copied();
+                    self.pos += 1;
+                    match escaped {
+                        Some(b'"') => out.push(b'"'),
+                        Some(b'\\') => out.push(b'\\'),
+                        Some(b'n') => out.push(b'\n'),
+                        Some(b't') => out.push(b'\t'),
+                        Some(b'r') => out.push(b'\r'),
+                        Some(other) => return Err(format!("unsupported escape \"\\{}\"", other as char)),
+                        None => break,
+                    }
+                }
+                _ => out.push(b),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let start = self.pos;
+        while let Some(&b) = self.input.get(self.pos) {
+            self.pos += 1;
+            if b == b'\'' {
+                return String::from_utf8(self.input[start..self.pos - 1].to_vec())
+                    .map_err(|_| "invalid UTF-8 in string".to_string());
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.input.get(self.pos) == Some(&b']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {}
+                _ => return Err("expected \",\" or \"]\" in array".to_string()),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while let Some(&b) = self.input.get(self.pos) {
+            if b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.' | b'_') {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let token = std::str::from_utf8(&self.input[start..self.pos]).unwrap_or("");
+        if token.is_empty() {
+            return Err(format!("invalid value \"{}\"", String::from_utf8_lossy(&self.input[start..])));
+        }
+        // Underscores are only allowed between digits
+        let bytes = token.as_bytes();
+        let bad_underscore = bytes.iter().enumerate().any(|(idx, &b)| {
+            b == b'_'
+                && !(idx > 0
+                    && bytes[idx - 1].is_ascii_digit()
+                    && bytes.get(idx + 1).is_some_and(u8::is_ascii_digit))
+        });
+        let cleaned = token.replace('_', "");
+        if !bad_underscore {
+            if let Ok(value) = cleaned.parse::<i64>() {
+                return Ok(Value::Integer(value));
+            }
+            let is_float = cleaned.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'));
+            if is_float {
+                if let Ok(value) = cleaned.parse::<f64>() {
+                    return Ok(Value::Float(value));
+                }
+            }
+        }
+        Err(format!("invalid value \"{}\" (strings need quotes)", token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, Error> {
+        Config::parse(text, &Registry::with_defaults())
+    }
+
+    #[test]
+    fn parses_tables_arrays_and_numbers() {
+        let config = parse(
+            "interval_ms = 1_500 # faster\n\
+             format = \"pretty\"\n\
+             adaptive = true\n\
+             [collectors.network]\n\
+             ref_bps = 1.25e9\n\
+             exclude = [\n  \"tun*\", # vpn\n  'wg#*',\n]\n\
+             [collectors.suspend]\n\
+             enabled = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.interval, Some(Duration::from_millis(1500)));
+        assert_eq!(config.format, Format::Pretty);
+        assert!(config.adaptive);
+        let network = config.collector("network").unwrap();
+        assert_eq!(network.ref_bps, Some(1.25e9));
+        assert_eq!(network.exclude, vec!["tun*".to_string(), "wg#*".to_string()]);
+        assert!(!config.collector("suspend").unwrap().enabled);
+    }
+
+    #[test]
+    fn errors_point_at_line_and_key() {
+        let err = parse("\n[collectors.network]\nref_bps = \"fast\"\n").unwrap_err();
+        assert_eq!(err.to_string(), "3: collectors.network.ref_bps: expected a number, found a string");
+
+        let err = parse("[collectors.cpu]\nexclude = []\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.key.as_deref(), Some("collectors.cpu.exclude"));
+
+        let err = parse("[collectors.gpu]\nenabled = true\n").unwrap_err();
+        assert!(err.message.starts_with("unknown collector \"gpu\""));
+
+        let err = parse("interval_ms = 0\n").unwrap_err();
+        assert_eq!(err.to_string(), "1: interval_ms: must be at least 1 (milliseconds)");
+
+        let err = parse("format = json\n").unwrap_err();
+        assert_eq!(err.to_string(), "1: format: invalid value \"json\" (strings need quotes)");
+    }
+}
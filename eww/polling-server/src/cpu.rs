@@ -0,0 +1,291 @@
+//! CPU usage from /proc/stat, with offline cores reported from sysfs.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::collector::{Activity, Collector};
+use crate::source::{ProcSource, Roots};
+use crate::{counter_delta, itoa_usize, push_opt_u32};
+
+/// Path to /proc/stat for CPU metrics
+pub const PROC_STAT_PATH: &str = "/proc/stat";
+/// CPU numbers the kernel can ever bring up (e.g., "0-63")
+pub const CPU_POSSIBLE_PATH: &str = "/sys/devices/system/cpu/possible";
+/// CPU numbers physically present; those missing from /proc/stat are offline
+pub const CPU_PRESENT_PATH: &str = "/sys/devices/system/cpu/present";
+/// Average usage percentage below which the machine counts as idle
+const IDLE_USAGE: u32 = 10;
+
+#[derive(Clone, Copy)]
+/// CPU counter values from /proc/stat (user, nice, system, idle, etc.)
+pub struct CpuCounters {
+    /// Total ticks (sum of all modes)
+    total: u64,
+    /// Idle ticks
+    idle: u64,
+}
+
+/// CPU metric entry for output
+pub struct CpuEntry {
+    /// CPU identifier (e.g., "cpu0", "cpu1")
+    pub id: String,
+    /// Usage percentage (0-100), `None` while the core is offline
+    pub usage: Option<u32>,
+    /// Counters went backwards; usage is 0 for this sample
+    pub reset: bool,
+}
+
+/// Parse CPU statistics from /proc/stat and calculate usage percentages.
+/// Uses Vec-based O(1) storage indexed by CPU number for fast lookups.
+/// Present cores missing from /proc/stat are offline; they are reported without
+/// usage and their counters dropped, so the first sample back online is a baseline.
+#[inline]
+pub fn collect_cpu(
+    data: &[u8],
+    prev: &mut Vec<Option<CpuCounters>>,
+    present: &[usize],
+    entries: &mut Vec<CpuEntry>,
+) {
+    let mut line_start = 0;
+    // /proc/stat lists cores in ascending order; offline ones are merged in between
+    let mut present_pos = 0;
+    
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'\n' || i == data.len() - 1 {
+            let end = if byte == b'\n' { i } else { i + 1 };
+            let line = &data[line_start..end];
+            
+            let Some((cpu_idx, fields)) = parse_cpu_line(line) else {
+                line_start = i + 1;
+                continue;
+            };
+            
+            while present_pos < present.len() && present[present_pos] < cpu_idx {
+                push_offline_cpu(present[present_pos], prev, entries);
+                present_pos += 1;
+            }
+            if present.get(present_pos) == Some(&cpu_idx) {
+                present_pos += 1;
+            }
+            if cpu_idx >= prev.len() {
+                prev.resize(cpu_idx + 1, None);
+            }
+            
+            // Parse numbers
+            let mut total: u64 = 0;
+            let mut idle: u64 = 0;
+            let mut field = 0;
+            let mut num = 0u64;
+            let mut in_num = false;
+            
+            for &b in fields {
+                if b.is_ascii_digit() {
+                    num = num.wrapping_mul(10).wrapping_add((b - b'0') as u64);
+                    in_num = true;
+                } else if in_num {
+                    total += num;
+                    if field == 3 {
+                        idle = num;
+                    }
+                    if field > 8 {
+                        break;
+                    }
+                    field += 1;
+                    num = 0;
+                    in_num = false;
+                }
+            }
+            
+            // O(1) lookup instead of O(hash) HashMap lookup
+            let (usage, reset) = match prev[cpu_idx].map(|p| (p, counter_delta(p.total, total))) {
+                Some((_, None)) => (Some(0), true),
+                Some((prev_sample, Some(total_diff))) if total_diff > 0 => {
+                    let idle_diff = idle.saturating_sub(prev_sample.idle);
+                    let active = total_diff.saturating_sub(idle_diff);
+                    (Some((100 * active / total_diff) as u32), false)
+                }
+                _ => (Some(0), false),
+            };
+            
+            prev[cpu_idx] = Some(CpuCounters { total, idle });
+            
+            entries.push(CpuEntry { id: cpu_id(cpu_idx), usage, reset });
+            
+            line_start = i + 1;
+        }
+    }
+    for &cpu_idx in &present[present_pos..] {
+        push_offline_cpu(cpu_idx, prev, entries);
+    }
+}
+
+/// Report an offline core and forget its counters.
+#[inline]
+fn push_offline_cpu(cpu_idx: usize, prev: &mut [Option<CpuCounters>], entries: &mut Vec<CpuEntry>) {
+    if let Some(counters) = prev.get_mut(cpu_idx) {
+        *counters = None;
+    }
+    entries.push(CpuEntry { id: cpu_id(cpu_idx), usage: None, reset: false });
+}
+
+/// Build a "cpuN" id manually without format! macro overhead.
+#[inline]
+fn cpu_id(cpu_idx: usize) -> String {
+    let mut id = String::with_capacity(8);
+    id.push_str("cpu");
+    itoa_usize(&mut id, cpu_idx);
+    id
+}
+
+/// Parse a sysfs CPU list ("0-3,8,10-11") into CPU numbers.
+pub fn parse_cpu_list(data: &[u8]) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    let text = std::str::from_utf8(data).unwrap_or("");
+    for range in text.trim().split(',').filter(|r| !r.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
+            cpus.extend(start..=end);
+        }
+    }
+    cpus
+}
+
+/// Read a sysfs CPU list file; `None` if it is missing or empty.
+pub fn read_cpu_list(path: &Path) -> Option<Vec<usize>> {
+    let cpus = parse_cpu_list(&fs::read(path).ok()?);
+    (!cpus.is_empty()).then_some(cpus)
+}
+
+/// Split a per-core "cpuN ..." line of /proc/stat into the CPU number and its counter fields.
+/// The aggregate "cpu" line and non-CPU lines yield `None`.
+#[inline]
+pub fn parse_cpu_line(line: &[u8]) -> Option<(usize, &[u8])> {
+    if !line.starts_with(b"cpu") || line.len() < 5 || !line[3].is_ascii_digit() {
+        return None;
+    }
+    
+    // Extract cpu number - cpu0, cpu1, etc.
+    let mut cpu_idx = 0usize;
+    let mut pos = 3;
+    while pos < line.len() && line[pos].is_ascii_digit() {
+        cpu_idx = cpu_idx * 10 + (line[pos] - b'0') as usize;
+        pos += 1;
+    }
+    
+    // Skip to first space
+    while pos < line.len() && line[pos] != b' ' && line[pos] != b'\t' {
+        pos += 1;
+    }
+    Some((cpu_idx, &line[pos..]))
+}
+
+/// CPU numbers listed in /proc/stat (online cores), in file order.
+pub fn cpu_indices(data: &[u8]) -> Vec<usize> {
+    data.split(|&b| b == b'\n')
+        .filter_map(parse_cpu_line)
+        .map(|(cpu_idx, _)| cpu_idx)
+        .collect()
+}
+
+/// Per-core usage ("c")
+pub struct CpuCollector {
+    source: ProcSource,
+    /// Previous counters indexed by CPU number, sized for every possible core
+    prev: Vec<Option<CpuCounters>>,
+    /// Cores physically present, in ascending order
+    present: Vec<usize>,
+    entries: Vec<CpuEntry>,
+}
+
+impl Default for CpuCollector {
+    fn default() -> Self {
+        CpuCollector {
+            // /proc/stat: ~5.5KB on a 16-core machine
+            source: ProcSource::new(PROC_STAT_PATH, 8192),
+            prev: Vec::new(),
+            present: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Collector for CpuCollector {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn key(&self) -> &'static str {
+        "c"
+    }
+
+    /// Frequent enough for a responsive usage graph; /proc/stat is cheap to read
+    fn interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.source.relocate(roots);
+        let online = self.source.read().map(cpu_indices).unwrap_or_default();
+        // Cores offline at startup are only known from sysfs
+        self.present = read_cpu_list(&roots.resolve(CPU_PRESENT_PATH)).unwrap_or_else(|| online.clone());
+        let possible = read_cpu_list(&roots.resolve(CPU_POSSIBLE_PATH)).unwrap_or(online);
+        // Use Vec instead of HashMap for CPU cores - O(1) lookup instead of O(hash)
+        // Sized for every possible core so hotplug never reallocates
+        self.prev = vec![None; possible.last().map_or(0, |&cpu| cpu + 1)];
+        self.entries = Vec::with_capacity(self.present.len());
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, _elapsed: f64) {
+        self.entries.clear();
+        match self.source.read() {
+            Some(data) => collect_cpu(data, &mut self.prev, &self.present, &mut self.entries),
+            // The first sample after recovery would otherwise span the whole outage
+            None => self.prev.fill(None),
+        }
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        out.push('[');
+        for (idx, entry) in self.entries.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str("[\"");
+            out.push_str(&entry.id);
+            out.push_str("\",");
+            push_opt_u32(out, entry.usage);
+            out.push_str(if entry.reset { ",true]" } else { ",false]" });
+        }
+        out.push(']');
+    }
+
+    fn error(&self) -> Option<&str> {
+        self.source.error()
+    }
+
+    fn reset_baselines(&mut self) {
+        self.prev.fill(None);
+    }
+
+    /// Idle while the average usage of the online cores stays low
+    fn activity(&self) -> Activity {
+        let (sum, count) = self
+            .entries
+            .iter()
+            .filter_map(|entry| entry.usage)
+            .fold((0, 0), |(sum, count), usage| (sum + usage, count + 1));
+        if count == 0 {
+            return Activity::Unknown;
+        }
+        if sum / count >= IDLE_USAGE {
+            Activity::Busy
+        } else {
+            Activity::Idle
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Registry of network interfaces and block devices seen in /proc.
+//!
+//! Each device name is allocated once, when the device first shows up, shared
+//! with the collectors' entries and dropped with its counters when it
+//! disappears. Both transitions are reported as events so the bar can animate
+//! devices coming and going.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Kind of device, reported with its events
+#[derive(Clone, Copy)]
+pub enum DeviceClass {
+    Net,
+    Disk,
+}
+
+impl DeviceClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceClass::Net => "net",
+            DeviceClass::Disk => "disk",
+        }
+    }
+}
+
+/// Device appearance or disappearance for output
+pub struct DeviceEvent {
+    /// `true` when the device appeared, `false` when it vanished
+    pub added: bool,
+    pub class: DeviceClass,
+    pub name: String,
+}
+
+struct Device<C> {
+    /// Name shared with the entries of every sample
+    name: Rc<str>,
+    /// Previous counters, `None` until the first sample or after a baseline reset
+    counters: Option<C>,
+    /// Generation of the last sample that listed the device
+    seen: u64,
+}
+
+/// Devices of one class with their previous counters
+pub struct DeviceRegistry<C> {
+    class: DeviceClass,
+    devices: HashMap<Rc<str>, Device<C>>,
+    /// Incremented per sample; devices not seen in the current one are evicted
+    generation: u64,
+}
+
+impl<C> DeviceRegistry<C> {
+    pub fn new(class: DeviceClass) -> Self {
+        DeviceRegistry {
+            class,
+            devices: HashMap::with_capacity(16),
+            generation: 0,
+        }
+    }
+
+    /// Start a new sample.
+    #[inline]
+    pub fn begin(&mut self) {
+        self.generation += 1;
+    }
+
+    /// Registered name and previous counters of `name`, registering the device if
+    /// it is new. Devices present at startup are not reported as added.
+    #[inline]
+    pub fn counters(&mut self, name: &str, events: &mut Vec<DeviceEvent>) -> (&Rc<str>, &mut Option<C>) {
+        if !self.devices.contains_key(name) {
+            if self.generation > 1 {
+                events.push(DeviceEvent {
+                    added: true,
+                    class: self.class,
+                    name: name.to_string(),
+                });
+            }
+            let name: Rc<str> = Rc::from(name);
+            let device = Device {
+                name: Rc::clone(&name),
+                counters: None,
+                seen: 0,
+            };
+            self.devices.insert(name, device);
+        }
+        let device = self.devices.get_mut(name).expect("device registered above");
+        device.seen = self.generation;
+        (&device.name, &mut device.counters)
+    }
+
+    /// Evict devices missing from the current sample.
+    #[inline]
+    pub fn finish(&mut self, events: &mut Vec<DeviceEvent>) {
+        let generation = self.generation;
+        let class = self.class;
+        self.devices.retain(|name, device| {
+            if device.seen == generation {
+                return true;
+            }
+            events.push(DeviceEvent {
+                added: false,
+                class,
+                name: name.to_string(),
+            });
+            false
+        });
+    }
+
+    /// Drop all previous counters so the next sample becomes a new baseline.
+    pub fn reset_baselines(&mut self) {
+        for device in self.devices.values_mut() {
+            device.counters = None;
+        }
+    }
+}
+
+/// Device name filter from `include`/`exclude` globs (`*` and `?`)
+#[derive(Default)]
+pub struct DeviceFilter {
+    /// Globs to keep; empty keeps every device
+    include: Vec<String>,
+    /// Globs to drop, checked after `include`
+    exclude: Vec<String>,
+}
+
+impl DeviceFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        DeviceFilter { include, exclude }
+    }
+
+    /// Whether `name` passes the filter.
+    #[inline]
+    pub fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob_match(glob, name)))
+            && !self.exclude.iter().any(|glob| glob_match(glob, name))
+    }
+}
+
+/// Match `name` against a glob where `*` matches any run and `?` one byte.
+pub fn glob_match(glob: &str, name: &str) -> bool {
+    let (glob, name) = (glob.as_bytes(), name.as_bytes());
+    let (mut g, mut n) = (0, 0);
+    // Position after the last `*` and the name position it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some(b'*') => {
+                star = Some((g + 1, n));
+                g += 1;
+            }
+            Some(&b) if b == b'?' || b == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, matched)) => {
+                    g = after;
+                    n = matched + 1;
+                    star = Some((after, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&b| b == b'*')
+}
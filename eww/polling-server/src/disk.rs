@@ -0,0 +1,232 @@
+//! Disk throughput from /proc/diskstats.
+
+use std::io;
+use std::rc::Rc;
+
+use crate::collector::Collector;
+use crate::config::CollectorConfig;
+use crate::devices::{DeviceClass, DeviceEvent, DeviceFilter, DeviceRegistry};
+use crate::source::{ProcSource, Roots};
+use crate::{counter_delta, ftoa_f64, itoa_u8, parse_number_from_line, rate_to_level, MIN_ELAPSED};
+
+/// Path to /proc/diskstats for disk metrics
+pub const DISKSTATS_PATH: &str = "/proc/diskstats";
+/// Reference bandwidth for disk level calculation (600 Mbps)
+pub const DISK_REF_BPS: f64 = 600_000_000.0;
+/// Disk sector size in bytes
+pub const DISK_SECTOR_SIZE: u64 = 512;
+
+#[derive(Clone, Copy)]
+/// Disk counter values
+pub struct DiskCounters {
+    /// Bytes read
+    read: u64,
+    /// Bytes written
+    write: u64,
+}
+
+/// Disk device entry for output
+pub struct DiskEntry {
+    /// Device name
+    pub device: Rc<str>,
+    /// Read level (0-10)
+    pub read_level: u8,
+    /// Write level (0-10)
+    pub write_level: u8,
+    /// Read rate in MiB/s
+    pub read_mib_s: f64,
+    /// Write rate in MiB/s
+    pub write_mib_s: f64,
+    /// Counters were reset (e.g., device replugged); rates are 0 for this sample
+    pub reset: bool,
+}
+
+/// Parse disk I/O counters from /proc/diskstats.
+/// Returns (device, read_sectors, write_sectors) with names borrowed from `data`.
+#[inline]
+pub fn parse_disks(data: &[u8]) -> Vec<(&str, u64, u64)> {
+    let mut result: Vec<(&str, u64, u64)> = Vec::with_capacity(16);
+    
+    for line in data.split(|&b| b == b'\n') {
+        // Fields: major minor name reads merged sectors_read ms writes merged sectors_written ...
+        let mut fields = line.split(|b| b.is_ascii_whitespace()).filter(|f| !f.is_empty());
+        let Some(name) = fields.nth(2).and_then(|f| std::str::from_utf8(f).ok()) else {
+            continue;
+        };
+        let read_sectors = fields.nth(2).map_or(0, parse_number_from_line);
+        let write_sectors = fields.nth(3).map_or(0, parse_number_from_line);
+        
+        // Skip pseudo-devices
+        if name.starts_with("loop") || name.starts_with("ram") || name.starts_with("dm-") {
+            continue;
+        }
+        
+        // Skip partitions (ends with digit and contains p or starts with s/h/v)
+        let last_byte = *name.as_bytes().last().unwrap_or(&0);
+        if last_byte.is_ascii_digit() && 
+           (name.contains('p') || matches!(name.as_bytes().first(), Some(&b's') | Some(&b'h') | Some(&b'v'))) {
+            continue;
+        }
+        
+        result.push((name, read_sectors, write_sectors));
+    }
+    result
+}
+
+/// Calculate disk I/O throughput rates and populate entries.
+/// Requires previous counters for rate calculation; levels are relative to `ref_bps`.
+#[inline]
+pub fn calculate_disk_rates(
+    elapsed: f64,
+    ref_bps: f64,
+    parsed: &[(&str, u64, u64)],
+    registry: &mut DeviceRegistry<DiskCounters>,
+    entries: &mut Vec<DiskEntry>,
+    events: &mut Vec<DeviceEvent>,
+) {
+    let elapsed = elapsed.max(MIN_ELAPSED);
+    
+    registry.begin();
+    for &(name, read_sectors, write_sectors) in parsed {
+        let (device, counters) = registry.counters(name, events);
+        let counters = counters.get_or_insert(DiskCounters {
+            read: read_sectors,
+            write: write_sectors,
+        });
+        
+        let sector_rate = |sectors: u64| (sectors * DISK_SECTOR_SIZE) as f64 / elapsed;
+        let (read_rate, write_rate, reset) =
+            match (counter_delta(counters.read, read_sectors), counter_delta(counters.write, write_sectors)) {
+                (Some(read), Some(write)) => (sector_rate(read), sector_rate(write), false),
+                _ => (0.0, 0.0, true),
+            };
+        
+        counters.read = read_sectors;
+        counters.write = write_sectors;
+        
+        entries.push(DiskEntry {
+            device: Rc::clone(device),
+            read_level: rate_to_level(read_rate, ref_bps),
+            write_level: rate_to_level(write_rate, ref_bps),
+            read_mib_s: read_rate / 1_048_576.0,
+            write_mib_s: write_rate / 1_048_576.0,
+            reset,
+        });
+    }
+    registry.finish(events);
+}
+
+/// Collect disk statistics: parse and calculate rates.
+/// Wrapper for convenience - calls parse_disks and calculate_disk_rates.
+#[inline]
+pub fn collect_disks(
+    elapsed: f64,
+    data: &[u8],
+    registry: &mut DeviceRegistry<DiskCounters>,
+    entries: &mut Vec<DiskEntry>,
+    events: &mut Vec<DeviceEvent>,
+) {
+    let parsed = parse_disks(data);
+    calculate_disk_rates(elapsed, DISK_REF_BPS, &parsed, registry, entries, events);
+}
+
+/// Per-device throughput ("d"), with devices coming and going reported as device events
+pub struct DiskCollector {
+    source: ProcSource,
+    devices: DeviceRegistry<DiskCounters>,
+    entries: Vec<DiskEntry>,
+    events: Vec<DeviceEvent>,
+    /// Reference bandwidth for the levels, bytes/s
+    ref_bps: f64,
+    filter: DeviceFilter,
+}
+
+impl Default for DiskCollector {
+    fn default() -> Self {
+        DiskCollector {
+            // /proc/diskstats: ~300B
+            source: ProcSource::new(DISKSTATS_PATH, 4096),
+            devices: DeviceRegistry::new(DeviceClass::Disk),
+            entries: Vec::with_capacity(16),
+            events: Vec::new(),
+            ref_bps: DISK_REF_BPS,
+            filter: DeviceFilter::default(),
+        }
+    }
+}
+
+impl Collector for DiskCollector {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn key(&self) -> &'static str {
+        "d"
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.source.relocate(roots);
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, elapsed: f64) {
+        self.entries.clear();
+        match self.source.read() {
+            Some(data) => {
+                let mut parsed = parse_disks(data);
+                parsed.retain(|&(name, _, _)| self.filter.matches(name));
+                calculate_disk_rates(elapsed, self.ref_bps, &parsed, &mut self.devices, &mut self.entries, &mut self.events);
+            }
+            None => self.devices.reset_baselines(),
+        }
+        self.entries.sort_by(|a, b| a.device.cmp(&b.device));
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        out.push('[');
+        for (idx, entry) in self.entries.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str("[\"");
+            out.push_str(&entry.device);
+            out.push_str("\",");
+            itoa_u8(out, entry.read_level);
+            out.push(',');
+            itoa_u8(out, entry.write_level);
+            out.push(',');
+            ftoa_f64(out, entry.read_mib_s, 2);
+            out.push(',');
+            ftoa_f64(out, entry.write_mib_s, 2);
+            out.push_str(if entry.reset { ",true]" } else { ",false]" });
+        }
+        out.push(']');
+    }
+
+    fn options(&self) -> &'static [&'static str] {
+        &["ref_bps", "include", "exclude"]
+    }
+
+    fn configure(&mut self, config: &CollectorConfig) {
+        self.ref_bps = config.ref_bps.unwrap_or(DISK_REF_BPS);
+        self.filter = DeviceFilter::new(config.include.clone(), config.exclude.clone());
+    }
+
+    fn error(&self) -> Option<&str> {
+        self.source.error()
+    }
+
+    fn reset_baselines(&mut self) {
+        self.devices.reset_baselines();
+    }
+
+    fn device_events(&self) -> &[DeviceEvent] {
+        &self.events
+    }
+
+    fn clear_device_events(&mut self) {
+        self.events.clear();
+    }
+}
@@ -0,0 +1,226 @@
+//! Single-threaded epoll loop: a timerfd for the collector schedule and every
+//! event source (IPC sockets, netlink, signalfd) on one thread.
+//!
+//! The timer is armed with absolute CLOCK_MONOTONIC deadlines, so ticks don't
+//! accumulate the time spent sampling, and the process only wakes up when a
+//! collector is due or a source has something to read.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Instant;
+
+use crate::events::Event;
+
+/// Token of the schedule timer; sources use their slot index
+const TIMER_TOKEN: u64 = u64::MAX;
+/// Token of a descriptor waited on for writability
+const WRITABLE_TOKEN: u64 = u64::MAX - 1;
+/// Readiness events handled per `epoll_wait`
+const MAX_EVENTS: usize = 16;
+
+/// Something to wait on in the event loop
+pub trait EventSource {
+    /// Name used when reporting errors (e.g., "sway ipc")
+    fn name(&self) -> &'static str;
+
+    /// Descriptor to wait on for readability. It may change after `start` or
+    /// `ready`, e.g., from a socket to a reconnect timer.
+    fn fd(&self) -> RawFd;
+
+    /// Push the initial state; called once when the source is added.
+    fn start(&mut self, _updates: &mut Vec<Event>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The descriptor is readable: consume what is available and push updates.
+    /// An error is reported once and drops the source.
+    fn ready(&mut self, updates: &mut Vec<Event>) -> io::Result<()>;
+}
+
+struct Slot {
+    source: Box<dyn EventSource>,
+    /// Descriptor currently registered with epoll
+    fd: RawFd,
+}
+
+/// epoll instance with the schedule timer and the registered sources
+pub struct EventLoop {
+    epoll: OwnedFd,
+    timer: OwnedFd,
+    /// Sources by token; dropped sources leave `None` so tokens stay stable
+    slots: Vec<Option<Slot>>,
+    /// Descriptor registered by `watch_writable`
+    writable: Option<RawFd>,
+    events: Vec<libc::epoll_event>,
+}
+
+impl EventLoop {
+    pub fn new() -> io::Result<Self> {
+        let epoll = cvt(unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) })?;
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        let timer = timerfd()?;
+        let event_loop = EventLoop {
+            epoll,
+            timer,
+            slots: Vec::new(),
+            writable: None,
+            events: vec![libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS],
+        };
+        event_loop.ctl(libc::EPOLL_CTL_ADD, event_loop.timer.as_raw_fd(), TIMER_TOKEN)?;
+        Ok(event_loop)
+    }
+
+    /// Start `source`, pushing its initial state to `updates`, and wait on it from now on.
+    pub fn add(&mut self, mut source: Box<dyn EventSource>, updates: &mut Vec<Event>) -> io::Result<()> {
+        source.start(updates)?;
+        let fd = source.fd();
+        let token = self.slots.len() as u64;
+        self.ctl(libc::EPOLL_CTL_ADD, fd, token)?;
+        self.slots.push(Some(Slot { source, fd }));
+        Ok(())
+    }
+
+    /// Also wake up when `fd` becomes writable, while `wanted`; e.g., stdout with
+    /// the rest of a line the reader hasn't taken yet.
+    pub fn watch_writable(&mut self, fd: RawFd, wanted: bool) -> io::Result<()> {
+        match (self.writable, wanted) {
+            (None, true) => {
+                self.ctl_events(libc::EPOLL_CTL_ADD, fd, WRITABLE_TOKEN, libc::EPOLLOUT as u32)?;
+                self.writable = Some(fd);
+            }
+            (Some(fd), false) => {
+                self.writable = None;
+                self.ctl(libc::EPOLL_CTL_DEL, fd, WRITABLE_TOKEN)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Wait until `deadline` or until a source is readable, pushing source updates
+    /// to `updates`. Returns `true` once the deadline has passed; a writable
+    /// descriptor just returns `false`.
+    pub fn wait(&mut self, deadline: Instant, updates: &mut Vec<Event>) -> io::Result<bool> {
+        arm(&self.timer, deadline)?;
+
+        let count = loop {
+            let rc = unsafe {
+                libc::epoll_wait(self.epoll.as_raw_fd(), self.events.as_mut_ptr(), MAX_EVENTS as i32, -1)
+            };
+            match cvt(rc) {
+                Ok(count) => break count as usize,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+
+        let mut expired = false;
+        for idx in 0..count {
+            let token = self.events[idx].u64;
+            if token == TIMER_TOKEN {
+                // Drain the expiration count so the timer is no longer readable
+                let mut expirations = [0u8; 8];
+                unsafe { libc::read(self.timer.as_raw_fd(), expirations.as_mut_ptr() as *mut libc::c_void, 8) };
+                expired = true;
+            } else if token != WRITABLE_TOKEN {
+                self.dispatch(token, updates);
+            }
+        }
+        Ok(expired || Instant::now() >= deadline)
+    }
+
+    /// Let the source of `token` read, then follow its descriptor if it changed.
+    fn dispatch(&mut self, token: u64, updates: &mut Vec<Event>) {
+        let Some(slot) = self.slots.get_mut(token as usize).and_then(Option::as_mut) else {
+            return;
+        };
+        let result = slot.source.ready(updates);
+        let (name, old_fd, new_fd) = (slot.source.name(), slot.fd, slot.source.fd());
+
+        if let Err(err) = result {
+            eprintln!("polling-server: {}: {}", name, err);
+            let _ = self.ctl(libc::EPOLL_CTL_DEL, old_fd, token);
+            self.slots[token as usize] = None;
+            return;
+        }
+
+        // A closed descriptor leaves epoll on its own and its number may be
+        // reused by the replacement, so re-register whenever in doubt
+        if new_fd != old_fd {
+            let _ = self.ctl(libc::EPOLL_CTL_DEL, old_fd, token);
+        }
+        let registered = self
+            .ctl(libc::EPOLL_CTL_MOD, new_fd, token)
+            .or_else(|_| self.ctl(libc::EPOLL_CTL_ADD, new_fd, token));
+        if let Err(err) = registered {
+            eprintln!("polling-server: {}: {}", name, err);
+            self.slots[token as usize] = None;
+            return;
+        }
+        if let Some(slot) = self.slots[token as usize].as_mut() {
+            slot.fd = new_fd;
+        }
+    }
+
+    fn ctl(&self, op: libc::c_int, fd: RawFd, token: u64) -> io::Result<()> {
+        self.ctl_events(op, fd, token, libc::EPOLLIN as u32)
+    }
+
+    fn ctl_events(&self, op: libc::c_int, fd: RawFd, token: u64, events: u32) -> io::Result<()> {
+        let mut event = libc::epoll_event { events, u64: token };
+        cvt(unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut event) }).map(|_| ())
+    }
+}
+
+/// Non-blocking CLOCK_MONOTONIC timerfd, disarmed.
+pub fn timerfd() -> io::Result<OwnedFd> {
+    let fd = cvt(unsafe {
+        libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_NONBLOCK | libc::TFD_CLOEXEC)
+    })?;
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Arm `timer` to fire once at `deadline`; a deadline in the past fires at once.
+pub fn arm(timer: &OwnedFd, deadline: Instant) -> io::Result<()> {
+    // Instant is CLOCK_MONOTONIC, but opaque: go through the offset from now
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let ahead = deadline.saturating_duration_since(Instant::now());
+    let mut nanos = now.tv_nsec + ahead.subsec_nanos() as libc::c_long;
+    let mut secs = now.tv_sec + ahead.as_secs() as libc::time_t;
+    if nanos >= 1_000_000_000 {
+        secs += 1;
+        nanos -= 1_000_000_000;
+    }
+
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec { tv_sec: 0, tv_nsec: 0 },
+        // Never zero: that would disarm instead of firing
+        it_value: libc::timespec {
+            tv_sec: secs,
+            tv_nsec: nanos.max(1),
+        },
+    };
+    cvt(unsafe {
+        libc::timerfd_settime(timer.as_raw_fd(), libc::TFD_TIMER_ABSTIME, &spec, std::ptr::null_mut())
+    })
+    .map(|_| ())
+}
+
+/// Disarm `timer` and clear a pending expiration.
+pub fn disarm(timer: &OwnedFd) -> io::Result<()> {
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec { tv_sec: 0, tv_nsec: 0 },
+        it_value: libc::timespec { tv_sec: 0, tv_nsec: 0 },
+    };
+    cvt(unsafe { libc::timerfd_settime(timer.as_raw_fd(), 0, &spec, std::ptr::null_mut()) }).map(|_| ())
+}
+
+/// Error from a libc return value.
+fn cvt(rc: libc::c_int) -> io::Result<libc::c_int> {
+    if rc < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(rc)
+    }
+}
@@ -0,0 +1,116 @@
+//! State pushed by event-driven sources (compositor IPC and audio) on the event loop.
+
+use crate::compositor::{self, CompositorState, LayoutEntry, WindowEntry, WorkspaceEntry};
+use crate::pulse::AudioEntry;
+use crate::{itoa_u32, itoa_u64, push_json_str};
+
+/// Latest state pushed by event-driven sources
+#[derive(Default)]
+pub struct EventState {
+    pub compositor: CompositorState,
+    /// `None` until the audio server answers, and while it is unreachable
+    pub audio: Option<AudioEntry>,
+}
+
+/// Update pushed by an event-driven source (compositor IPC and audio), a kernel
+/// uevent, or a signal read from the signalfd
+pub enum Event {
+    Layout(LayoutEntry),
+    Workspaces(Vec<WorkspaceEntry>),
+    Window(Option<WindowEntry>),
+    Mode(Option<String>),
+    /// `None` while the audio server is unreachable
+    Audio(Option<AudioEntry>),
+    /// Kernel uevent of a subsystem a collector listens to
+    Uevent(&'static str),
+    /// SIGHUP: re-read the config
+    Reload,
+    /// SIGTERM or SIGINT: write a final payload and exit
+    Shutdown,
+}
+
+impl EventState {
+    /// Fold an event into the current state.
+    #[inline]
+    pub fn apply(&mut self, event: Event, title_width: usize) {
+        let compositor = &mut self.compositor;
+        match event {
+            Event::Layout(mut entry) => {
+                // The bar shows the first two letters, lowercase like the old script did
+                entry.name = entry.name.to_lowercase();
+                compositor.layout = Some(entry);
+            }
+            Event::Workspaces(entries) => compositor.workspaces = entries,
+            Event::Window(mut entry) => {
+                if let Some(window) = entry.as_mut() {
+                    compositor::truncate_chars(&mut window.title, title_width);
+                }
+                compositor.window = entry;
+            }
+            Event::Mode(name) => compositor.mode = name,
+            Event::Audio(entry) => self.audio = entry,
+            // Handled by the main loop
+            Event::Uevent(_) | Event::Reload | Event::Shutdown => {}
+        }
+    }
+
+    /// Append the event-driven sections ("w", "t", "l", "o", "a").
+    #[inline]
+    pub fn serialize(&self, out: &mut String) {
+        let compositor = &self.compositor;
+        out.push_str("\"w\":[");
+        for (idx, entry) in compositor.workspaces.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push('[');
+            if entry.id < 0 {
+                out.push('-');
+            }
+            itoa_u64(out, entry.id.unsigned_abs());
+            out.push(',');
+            push_json_str(out, &entry.name);
+            out.push_str(if entry.focused { ",true" } else { ",false" });
+            out.push_str(if entry.urgent { ",true]" } else { ",false]" });
+        }
+        out.push_str("],\"t\":");
+        if let Some(window) = &compositor.window {
+            out.push('[');
+            push_json_str(out, &window.title);
+            out.push(',');
+            push_json_str(out, &window.app_id);
+            out.push_str(if window.floating { ",true" } else { ",false" });
+            out.push_str(if window.fullscreen { ",true]" } else { ",false]" });
+        } else {
+            out.push_str("null");
+        }
+        out.push_str(",\"l\":");
+        if let Some(layout) = &compositor.layout {
+            out.push('[');
+            push_json_str(out, &layout.name);
+            out.push(',');
+            itoa_u32(out, layout.index);
+            out.push(']');
+        } else {
+            out.push_str("null");
+        }
+        out.push_str(",\"o\":");
+        if let Some(mode) = &compositor.mode {
+            push_json_str(out, mode);
+        } else {
+            out.push_str("null");
+        }
+        out.push_str(",\"a\":");
+        if let Some(audio) = &self.audio {
+            out.push('[');
+            itoa_u32(out, audio.volume);
+            out.push_str(if audio.muted { ",true," } else { ",false," });
+            push_json_str(out, &audio.sink);
+            out.push(',');
+            push_json_str(out, &audio.port);
+            out.push_str(if audio.mic_muted { ",true]" } else { ",false]" });
+        } else {
+            out.push_str("null");
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Native Hyprland IPC client.
+//!
+//! `.socket.sock` answers one request per connection (the same protocol
+//! `hyprctl` speaks), `.socket2.sock` streams `EVENT>>DATA` lines. Events only
+//! tell us *what* changed, so each one triggers a fresh JSON query.
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use crate::compositor::{LayoutEntry, WindowEntry, WorkspaceEntry, IPC_TIMEOUT};
+use crate::event_loop::EventSource;
+use crate::json::{self, Value};
+use crate::Event;
+
+/// Request socket, relative to the instance directory
+const REQUEST_SOCKET: &str = ".socket.sock";
+/// Event socket, relative to the instance directory
+const EVENT_SOCKET: &str = ".socket2.sock";
+/// Prefix of special (scratchpad) workspace names, hidden from the bar
+const SPECIAL_PREFIX: &str = "special:";
+
+/// Instance socket directory from the environment, if running inside Hyprland.
+pub fn socket_dir() -> Option<String> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok().filter(|s| !s.is_empty())?;
+    let runtime = env::var("XDG_RUNTIME_DIR").ok().filter(|s| !s.is_empty())?;
+    let dir = format!("{}/hypr/{}", runtime, signature);
+    Path::new(&dir).join(REQUEST_SOCKET).exists().then_some(dir)
+}
+
+/// Send one request on a fresh `.socket.sock` connection and read the reply to EOF.
+fn request(dir: &str, command: &str) -> io::Result<Vec<u8>> {
+    let mut stream = UnixStream::connect(Path::new(dir).join(REQUEST_SOCKET))?;
+    stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+    stream.write_all(command.as_bytes())?;
+    let mut reply = Vec::with_capacity(4096);
+    stream.read_to_end(&mut reply)?;
+    Ok(reply)
+}
+
+/// JSON request (`j/` prefix), `None` when the reply doesn't parse.
+fn request_json(dir: &str, command: &str) -> io::Result<Option<Value>> {
+    let reply = request(dir, &format!("j/{}", command))?;
+    Ok(json::parse(&reply))
+}
+
+/// Hyprland event stream (`.socket2.sock`), waited on by the event loop
+pub struct HyprlandSource {
+    dir: String,
+    /// Non-blocking, so a line cut short can't stall the event loop
+    stream: UnixStream,
+    /// Workspaces with urgent windows, cleared when visited
+    urgent: Vec<i64>,
+    /// Bytes read but not handled yet: the start of an unterminated line
+    pending: Vec<u8>,
+}
+
+impl HyprlandSource {
+    /// Connect to the event stream; the snapshots are taken by `start`.
+    pub fn connect(dir: &str) -> io::Result<Self> {
+        let stream = UnixStream::connect(Path::new(dir).join(EVENT_SOCKET))?;
+        stream.set_nonblocking(true)?;
+        Ok(HyprlandSource {
+            dir: dir.to_string(),
+            stream,
+            urgent: Vec::new(),
+            pending: Vec::with_capacity(4096),
+        })
+    }
+
+    /// Turn one `EVENT>>DATA` line into updates.
+    fn handle(&mut self, line: &[u8], updates: &mut Vec<Event>) -> io::Result<()> {
+        let dir = self.dir.as_str();
+        let urgent = &mut self.urgent;
+        let line = String::from_utf8_lossy(line);
+        let Some((name, data)) = line.trim_end().split_once(">>") else {
+            return Ok(());
+        };
+        match name {
+            "workspacev2" | "focusedmon" => {
+                // Visiting a workspace acknowledges its urgent windows
+                if let Some(active) = active_workspace_id(dir)? {
+                    urgent.retain(|&id| id != active);
+                }
+                updates.push(Event::Workspaces(workspaces(dir, urgent)?));
+                // Switching to an empty workspace emits no activewindow event
+                updates.push(Event::Window(active_window(dir)?));
+            }
+            "createworkspacev2" | "destroyworkspacev2" | "renameworkspace" | "moveworkspacev2" => {
+                updates.push(Event::Workspaces(workspaces(dir, urgent)?));
+            }
+            "urgent" => {
+                if let Some(id) = window_workspace(dir, data)? {
+                    if !urgent.contains(&id) {
+                        urgent.push(id);
+                    }
+                    updates.push(Event::Workspaces(workspaces(dir, urgent)?));
+                }
+            }
+            "activewindowv2" | "windowtitlev2" | "closewindow" | "fullscreen" | "changefloatingmode" => {
+                updates.push(Event::Window(active_window(dir)?));
+            }
+            "submap" => {
+                // An empty name means the default submap was restored
+                let submap = (!data.is_empty()).then(|| data.to_string());
+                updates.push(Event::Mode(submap));
+            }
+            "activelayout" => {
+                if let Some(layout) = keyboard_layout(dir)? {
+                    updates.push(Event::Layout(layout));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl EventSource for HyprlandSource {
+    fn name(&self) -> &'static str {
+        "hyprland ipc"
+    }
+
+    fn fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+
+    fn start(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        updates.push(Event::Workspaces(workspaces(&self.dir, &self.urgent)?));
+        updates.push(Event::Window(active_window(&self.dir)?));
+        if let Some(layout) = keyboard_layout(&self.dir)? {
+            updates.push(Event::Layout(layout));
+        }
+        updates.push(Event::Mode(current_submap(&self.dir)?));
+        Ok(())
+    }
+
+    fn ready(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        // Drain the socket, then handle every complete line; the remainder
+        // stays in `pending` until the rest arrives
+        let mut chunk = [0u8; 4096];
+        let mut closed = false;
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(len) => self.pending.extend_from_slice(&chunk[..len]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        // A failing query drops the source, so the buffer needn't survive the error
+        let mut pending = mem::take(&mut self.pending);
+        let mut start = 0;
+        while let Some(len) = pending[start..].iter().position(|&b| b == b'\n') {
+            self.handle(&pending[start..start + len], updates)?;
+            start += len + 1;
+        }
+        pending.drain(..start);
+        self.pending = pending;
+
+        if closed {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "event socket closed"));
+        }
+        Ok(())
+    }
+}
+
+/// All regular workspaces, sorted by id, with focus and urgency applied.
+fn workspaces(dir: &str, urgent: &[i64]) -> io::Result<Vec<WorkspaceEntry>> {
+    let active = active_workspace_id(dir)?;
+    let Some(list) = request_json(dir, "workspaces")? else {
+        return Ok(Vec::new());
+    };
+    let mut workspaces: Vec<WorkspaceEntry> = list
+        .as_array()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|workspace| {
+            let id = workspace.get("id")?.as_i64()?;
+            let name = workspace.get("name")?.as_str()?;
+            if name.starts_with(SPECIAL_PREFIX) {
+                return None;
+            }
+            Some(WorkspaceEntry {
+                id,
+                name: name.to_string(),
+                focused: Some(id) == active,
+                urgent: urgent.contains(&id),
+            })
+        })
+        .collect();
+    workspaces.sort_by_key(|ws| ws.id);
+    Ok(workspaces)
+}
+
+/// Id of the workspace on the focused monitor.
+fn active_workspace_id(dir: &str) -> io::Result<Option<i64>> {
+    Ok(request_json(dir, "activeworkspace")?
+        .as_ref()
+        .and_then(|ws| ws.get("id"))
+        .and_then(Value::as_i64))
+}
+
+/// Workspace of the window at `address` (hex, without the `0x` prefix in events).
+fn window_workspace(dir: &str, address: &str) -> io::Result<Option<i64>> {
+    let address = format!("0x{}", address.trim_start_matches("0x"));
+    Ok(request_json(dir, "clients")?.as_ref().and_then(|clients| {
+        clients
+            .as_array()?
+            .iter()
+            .find(|client| client.get("address").and_then(Value::as_str) == Some(address.as_str()))?
+            .get("workspace")?
+            .get("id")?
+            .as_i64()
+    }))
+}
+
+/// Focused window; Hyprland replies `{}` when nothing is focused.
+fn active_window(dir: &str) -> io::Result<Option<WindowEntry>> {
+    Ok(request_json(dir, "activewindow")?.as_ref().and_then(|window| {
+        Some(WindowEntry {
+            title: window.get("title")?.as_str()?.to_string(),
+            app_id: window.get("class").and_then(Value::as_str).unwrap_or("").to_string(),
+            floating: window.get("floating").and_then(Value::as_bool).unwrap_or(false),
+            fullscreen: window.get("fullscreen").is_some_and(fullscreen_state),
+        })
+    }))
+}
+
+/// `fullscreen` is a bool in older releases and a mode number (0 = none) since 0.42.
+fn fullscreen_state(value: &Value) -> bool {
+    value.as_bool().or_else(|| value.as_u64().map(|mode| mode > 0)).unwrap_or(false)
+}
+
+/// Active layout of the main keyboard.
+fn keyboard_layout(dir: &str) -> io::Result<Option<LayoutEntry>> {
+    Ok(request_json(dir, "devices")?.as_ref().and_then(|devices| {
+        let keyboards = devices.get("keyboards")?.as_array()?;
+        let keyboard = keyboards
+            .iter()
+            .find(|kb| kb.get("main").and_then(Value::as_bool) == Some(true))
+            .or_else(|| keyboards.first())?;
+        Some(LayoutEntry {
+            name: keyboard.get("active_keymap")?.as_str()?.to_string(),
+            // Older releases don't report the index
+            index: keyboard
+                .get("active_layout_index")
+                .and_then(Value::as_u64)
+                .unwrap_or(0) as u32,
+        })
+    }))
+}
+
+/// Current submap; `None` for the default one and on releases without the `submap` request.
+fn current_submap(dir: &str) -> io::Result<Option<String>> {
+    let reply = request(dir, "submap")?;
+    let name = String::from_utf8_lossy(reply.trim_ascii());
+    Ok(match name.as_ref() {
+        "" | "default" | "unknown request" => None,
+        name => Some(name.to_string()),
+    })
+}
+
+/// Send a command whose only reply is `ok` or an error message.
+fn run_command(dir: &str, command: &str) -> io::Result<()> {
+    let reply = request(dir, command)?;
+    if reply.trim_ascii() == b"ok" {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&reply).into_owned()))
+    }
+}
+
+/// Switch every keyboard to another layout (`next`, `prev` or an index).
+pub fn switch_layout(dir: &str, target: &str) -> io::Result<()> {
+    run_command(dir, &format!("switchxkblayout all {}", target))
+}
+
+/// Focus a workspace by number.
+pub fn focus_workspace(dir: &str, id: i64) -> io::Result<()> {
+    run_command(dir, &format!("dispatch workspace {}", id))
+}
@@ -0,0 +1,314 @@
+//! Minimal JSON reader for compositor IPC replies.
+//!
+//! Only what the IPC clients need: parse a complete document into a tree and
+//! look values up by key. No serializer - payload output is hand-written.
+
+/// Parsed JSON value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Object members in document order (objects in IPC replies are small)
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Look up an object member by key; `None` for missing keys and non-objects.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) if *n >= 0.0 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a complete JSON document. Returns `None` on any syntax error.
+pub fn parse(data: &[u8]) -> Option<Value> {
+    let mut parser = Parser { data, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_ws();
+    if parser.pos == data.len() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    #[inline]
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &[u8]) -> Option<()> {
+        if self.data[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_ws();
+        match self.peek()? {
+            b'{' => self.object(),
+            b'[' => self.array(),
+            b'"' => self.string().map(Value::String),
+            b't' => self.expect_literal(b"true").map(|_| Value::Bool(true)),
+            b'f' => self.expect_literal(b"false").map(|_| Value::Bool(false)),
+            b'n' => self.expect_literal(b"null").map(|_| Value::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn object(&mut self) -> Option<Value> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_ws();
+        if self.peek()? == b'}' {
+            self.pos += 1;
+            return Some(Value::Object(members));
+        }
+        loop {
+            self.skip_ws();
+            if self.peek()? != b'"' {
+                return None;
+            }
+            let key = self.string()?;
+            self.skip_ws();
+            if self.peek()? != b':' {
+                return None;
+            }
+            self.pos += 1;
+            let value = self.value()?;
+            members.push((key, value));
+            self.skip_ws();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b'}' => {
+                    self.pos += 1;
+                    return Some(Value::Object(members));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn array(&mut self) -> Option<Value> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.peek()? == b']' {
+            self.pos += 1;
+            return Some(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => {
+                    self.pos += 1;
+                    return Some(Value::Array(items));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.peek() {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.data[start..self.pos]).ok()?;
+        text.parse::<f64>().ok().map(Value::Number)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        // Opening quote
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let byte = self.peek()?;
+            self.pos += 1;
+            match byte {
+                b'"' => return String::from_utf8(out).ok(),
+                b'\\' => {
+                    let escape = self.peek()?;
+                    self.pos += 1;
+                    match escape {
+                        b'"' | b'\\' | b'/' => out.push(escape),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'u' => {
+                            let ch = self.unicode_escape()?;
+                            let mut utf8 = [0u8; 4];
+                            out.extend_from_slice(ch.encode_utf8(&mut utf8).as_bytes());
+                        }
+                        _ => return None,
+                    }
+                }
+                _ => out.push(byte),
+            }
+        }
+    }
+
+    /// Decode the hex digits after `\u`, joining UTF-16 surrogate pairs.
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high as u32).or(Some(char::REPLACEMENT_CHARACTER));
+        }
+        if self.data[self.pos..].starts_with(b"\\u") {
+            let escape = self.pos;
+            self.pos += 2;
+            let low = self.hex4()?;
+            if (0xDC00..0xE000).contains(&low) {
+                let code = 0x10000 + (((high as u32) - 0xD800) << 10) + ((low as u32) - 0xDC00);
+                return char::from_u32(code);
+            }
+            // Not the low half: the escape stands on its own
+            self.pos = escape;
+        }
+        Some(char::REPLACEMENT_CHARACTER)
+    }
+
+    fn hex4(&mut self) -> Option<u16> {
+        let digits = self.data.get(self.pos..self.pos + 4)?;
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        self.pos += 4;
+        let text = std::str::from_utf8(digits).ok()?;
+        u16::from_str_radix(text, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(json: &str) -> Option<String> {
+        parse(json.as_bytes())?.as_str().map(str::to_string)
+    }
+
+    #[test]
+    fn nested_objects_and_arrays() {
+        let value = parse(br#" {"a": [1, {"b": [true, false, null]}, []], "c": {}} "#).unwrap();
+        let items = value.get("a").and_then(Value::as_array).unwrap();
+        assert_eq!(items[0], Value::Number(1.0));
+        let inner = items[1].get("b").and_then(Value::as_array).unwrap();
+        assert_eq!(inner, [Value::Bool(true), Value::Bool(false), Value::Null]);
+        assert_eq!(items[2], Value::Array(Vec::new()));
+        assert_eq!(value.get("c"), Some(&Value::Object(Vec::new())));
+        assert_eq!(value.get("missing"), None);
+        assert!(parse(b"[1, [2]").is_none());
+        assert!(parse(br#"{"a" 1}"#).is_none());
+        assert!(parse(b"[1,]").is_none());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\"b\\c\/d\b\f\n\r\t""#).unwrap(), "a\"b\\c/d\u{8}\u{c}\n\r\t");
+        assert_eq!(string(r#""\u00e9t\u00C9""#).unwrap(), "\u{e9}t\u{c9}");
+        assert_eq!(string("\"d\u{e9}j\u{e0} vu\"").unwrap(), "déjà vu");
+        assert!(string(r#""\x""#).is_none());
+        assert!(string(r#""\u12""#).is_none());
+        assert!(string(r#""\u+123""#).is_none());
+        assert!(string(r#""unterminated"#).is_none());
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(string(r#""\ud83d\ude00""#).unwrap(), "\u{1f600}");
+        assert_eq!(string(r#""\uD834\uDD1E""#).unwrap(), "\u{1d11e}");
+        // Unpaired halves become U+FFFD
+        assert_eq!(string(r#""\ud83d""#).unwrap(), "\u{fffd}");
+        assert_eq!(string(r#""\ude00x""#).unwrap(), "\u{fffd}x");
+        assert_eq!(string(r#""\ud83dx""#).unwrap(), "\u{fffd}x");
+        // A high half followed by another escape keeps that escape
+        assert_eq!(string(r#""\ud83d\u0041""#).unwrap(), "\u{fffd}A");
+        assert_eq!(string(r#""\ud83d\ud83d\ude00""#).unwrap(), "\u{fffd}\u{1f600}");
+        assert_eq!(string(r#""\ud83d\n""#).unwrap(), "\u{fffd}\n");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse(b"0"), Some(Value::Number(0.0)));
+        assert_eq!(parse(b"-12"), Some(Value::Number(-12.0)));
+        assert_eq!(parse(b"3.25"), Some(Value::Number(3.25)));
+        assert_eq!(parse(b"1e3"), Some(Value::Number(1000.0)));
+        assert_eq!(parse(b"-2.5E-1"), Some(Value::Number(-0.25)));
+        assert_eq!(parse(b"4294967296").unwrap().as_u64(), Some(1 << 32));
+        assert_eq!(parse(b"-7").unwrap().as_u64(), None);
+        assert_eq!(parse(b"-7").unwrap().as_i64(), Some(-7));
+        assert!(parse(b"1.2.3").is_none());
+        assert!(parse(b"-").is_none());
+        assert!(parse(b"").is_none());
+    }
+
+    #[test]
+    fn trailing_garbage() {
+        assert!(parse(b"{} ").is_some());
+        assert!(parse(b"{}x").is_none());
+        assert!(parse(b"[1] [2]").is_none());
+        assert!(parse(b"true false").is_none());
+        assert!(parse(b"nullx").is_none());
+        assert!(parse(br#""a""b""#).is_none());
+    }
+}
@@ -0,0 +1,330 @@
+//! System metrics and desktop state for the eww bar.
+//!
+//! Every polled payload section comes from a [`Collector`]; the `polling-server`
+//! binary drives a [`Registry`] of them and merges in the state pushed by
+//! event-driven sources (compositor IPC, audio) before writing a JSON line.
+//! Everything runs on one thread around an epoll [`event_loop`].
+
+pub mod adaptive;
+pub mod battery;
+pub mod cli;
+pub mod collector;
+pub mod compositor;
+pub mod config;
+pub mod cpu;
+pub mod devices;
+pub mod disk;
+pub mod event_loop;
+pub mod events;
+mod hyprland;
+pub mod json;
+pub mod memory;
+pub mod network;
+pub mod output;
+pub mod profile;
+pub mod pulse;
+pub mod rapl;
+pub mod signals;
+pub mod source;
+pub mod suspend;
+mod sway;
+pub mod throttle;
+pub mod uevent;
+
+use std::io;
+
+pub use collector::{Activity, Collector, Registry};
+pub use events::{Event, EventState};
+
+/// Initial capacity for JSON payload buffer
+pub const PAYLOAD_CAPACITY: usize = 4096;
+/// Minimum elapsed time to avoid division by zero
+pub const MIN_ELAPSED: f64 = 1e-8;
+/// Largest share of a counter's range accepted as a wrap: the counter has to drop
+/// from its top quarter into its bottom quarter, anything else is a reset
+const COUNTER_WRAP_DIVISOR: u64 = 4;
+
+/// Read the start of a file with one pread64, leaving the file offset alone.
+/// Meant for small files known to fit `buf` (e.g., a sysfs attribute); anything
+/// past `buf.len()` is cut off, so use `pread_all` when the size is open.
+///
+/// # Arguments
+/// * `fd` - Open file descriptor (must be kept open by caller)
+/// * `buf` - Buffer to read into, not grown
+///
+/// # Returns
+/// Number of bytes read, or io::Error on failure
+#[inline]
+pub fn pread_file(fd: i32, buf: &mut [u8]) -> io::Result<usize> {
+    let bytes_read = unsafe { libc::pread64(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+    if bytes_read < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(bytes_read as usize)
+    }
+}
+
+/// Read a whole file with pread64, growing `buf` until EOF.
+/// Grown buffers are kept by the caller, so once they fit the file no further
+/// allocation happens; the trailing zero-length read is the only extra syscall.
+///
+/// # Arguments
+/// * `fd` - Open file descriptor (must be kept open by caller)
+/// * `buf` - Buffer to read into, grown as needed
+///
+/// # Returns
+/// Number of bytes read, or io::Error on failure
+#[inline]
+pub fn pread_all(fd: i32, buf: &mut Vec<u8>) -> io::Result<usize> {
+    let mut len = 0;
+    loop {
+        if len == buf.len() {
+            buf.resize((buf.len() * 2).max(4096), 0);
+        }
+        let bytes_read = unsafe {
+            libc::pread64(
+                fd,
+                buf[len..].as_mut_ptr() as *mut libc::c_void,
+                buf.len() - len,
+                len as libc::off64_t,
+            )
+        };
+        
+        if bytes_read < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if bytes_read == 0 {
+            return Ok(len);
+        }
+        len += bytes_read as usize;
+    }
+}
+
+/// Difference between two samples of a monotonic kernel counter, `None` when it was reset.
+/// Older drivers keep 32-bit counters; a drop from near 2^32 to near zero is a wrap, not a reset.
+#[inline]
+pub fn counter_delta(prev: u64, now: u64) -> Option<u64> {
+    wrapping_counter_delta(prev, now, u32::MAX as u64)
+}
+
+/// `counter_delta` for a counter that wraps after `max` instead of 2^32 - 1.
+#[inline]
+pub fn wrapping_counter_delta(prev: u64, now: u64, max: u64) -> Option<u64> {
+    if now >= prev {
+        return Some(now - prev);
+    }
+    if prev > max {
+        return None;
+    }
+    let wrapped = max - prev + now + 1;
+    (wrapped < max / COUNTER_WRAP_DIVISOR).then_some(wrapped)
+}
+
+#[inline]
+pub fn parse_number_from_line(line: &[u8]) -> u64 {
+    let mut num = 0u64;
+    let mut in_num = false;
+    for &byte in line {
+        if byte.is_ascii_digit() {
+            num = num.wrapping_mul(10).wrapping_add((byte - b'0') as u64);
+            in_num = true;
+        } else if in_num {
+            // Found first number, return it
+            return num;
+        }
+        // Skip non-digits until we find a number
+    }
+    num
+}
+
+/// Convert throughput rate to a 0-10 level indicator relative to reference.
+#[inline]
+pub fn rate_to_level(rate: f64, reference: f64) -> u8 {
+    if rate <= 0.0 || reference <= 0.0 {
+        return 0;
+    }
+    let ratio = (rate / reference).min(1.0);
+    let level = (ratio * 10.0).ceil() as u8;
+    level.min(10)
+}
+
+// Extreme optimization: inline number-to-string conversions
+#[inline]
+pub fn itoa_u8(s: &mut String, mut n: u8) {
+    if n == 0 {
+        s.push('0');
+        return;
+    }
+    let mut buf = [b'0'; 3];
+    let mut i = 3;
+    while n > 0 {
+        i -= 1;
+        buf[i] = b'0' + (n % 10);
+        n /= 10;
+    }
+    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
+}
+
+#[inline]
+pub fn itoa_usize(s: &mut String, mut n: usize) {
+    if n == 0 {
+        s.push('0');
+        return;
+    }
+    let mut buf = [b'0'; 20];
+    let mut i = 20;
+    while n > 0 {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
+}
+
+#[inline]
+pub fn itoa_u32(s: &mut String, mut n: u32) {
+    if n == 0 {
+        s.push('0');
+        return;
+    }
+    let mut buf = [b'0'; 10];
+    let mut i = 10;
+    while n > 0 {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
+}
+
+#[inline]
+pub fn itoa_u64(s: &mut String, mut n: u64) {
+    if n == 0 {
+        s.push('0');
+        return;
+    }
+    let mut buf = [b'0'; 20];
+    let mut i = 20;
+    while n > 0 {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
+}
+
+#[inline]
+pub fn ftoa_f64(s: &mut String, mut n: f64, prec: usize) {
+    if n < 0.0 {
+        s.push('-');
+        n = -n;
+    }
+    let int_part = n as u64;
+    itoa_u64(s, int_part);
+    s.push('.');
+    let mut frac = n - int_part as f64;
+    for _ in 0..prec {
+        frac *= 10.0;
+        let digit = frac as u8;
+        s.push((b'0' + digit) as char);
+        frac -= digit as f64;
+    }
+}
+
+/// Append a number, or null when absent.
+#[inline]
+pub fn push_opt_u32(s: &mut String, value: Option<u32>) {
+    match value {
+        Some(n) => itoa_u32(s, n),
+        None => s.push_str("null"),
+    }
+}
+
+/// Append a quoted JSON string, escaping quotes, backslashes and control characters.
+/// Needed for compositor-provided text; kernel device names are pushed raw.
+#[inline]
+pub fn push_json_str(s: &mut String, value: &str) {
+    s.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                s.push_str("\\u00");
+                s.push(char::from_digit((c as u32) >> 4, 16).unwrap_or('0'));
+                s.push(char::from_digit((c as u32) & 0xf, 16).unwrap_or('0'));
+            }
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+}
+
+/// Build the JSON payload from the collectors' last samples and the event-driven state.
+/// Avoids format! macro overhead by using inlined itoa_* and ftoa_* functions.
+#[inline]
+pub fn build_payload(out: &mut String, registry: &Registry, state: &EventState) {
+    out.clear();
+    out.reserve(PAYLOAD_CAPACITY);
+    out.push('{');
+    registry.serialize(out);
+    out.push(',');
+    state.serialize(out);
+    out.push('}');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const U32_MAX: u64 = u32::MAX as u64;
+
+    #[test]
+    fn counter_delta_counts_up() {
+        assert_eq!(counter_delta(10, 10), Some(0));
+        assert_eq!(counter_delta(10, 25), Some(15));
+        assert_eq!(counter_delta(U32_MAX, U32_MAX + 5), Some(5));
+    }
+
+    #[test]
+    fn counter_delta_wraps_32_bit() {
+        assert_eq!(counter_delta(U32_MAX, 0), Some(1));
+        assert_eq!(counter_delta(U32_MAX - 99, 100), Some(200));
+    }
+
+    #[test]
+    fn counter_delta_rejects_resets() {
+        // Far from the top of the range: the interface or driver was reset
+        assert_eq!(counter_delta(3_000_000_000, 10), None);
+        assert_eq!(counter_delta(1000, 10), None);
+        // 64-bit counters don't wrap within a lifetime
+        assert_eq!(counter_delta(U32_MAX + 1, 10), None);
+        assert_eq!(counter_delta(u64::MAX, 0), None);
+    }
+
+    #[test]
+    fn counter_delta_at_the_2_31_boundary() {
+        assert_eq!(counter_delta((1 << 31) - 1, 1 << 31), Some(1));
+        // Half the range away from the top is never a wrap
+        assert_eq!(counter_delta(1 << 31, 0), None);
+        assert_eq!(counter_delta((1 << 31) + 1, 0), None);
+        // The accepted window ends a quarter of the range past the top
+        let window = U32_MAX / COUNTER_WRAP_DIVISOR;
+        assert_eq!(counter_delta(U32_MAX, window - 2), Some(window - 1));
+        assert_eq!(counter_delta(U32_MAX, window - 1), None);
+    }
+
+    #[test]
+    fn wrapping_counter_delta_uses_the_range() {
+        assert_eq!(wrapping_counter_delta(990, 5, 999), Some(15));
+        assert_eq!(wrapping_counter_delta(500, 5, 999), None);
+        assert_eq!(wrapping_counter_delta(1500, 5, 999), None);
+    }
+}
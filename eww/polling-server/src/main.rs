@@ -1,836 +1,413 @@
-use std::collections::HashMap;
 use std::env;
-use std::fs::File;
 use std::io::{self, Write};
+use std::mem;
 use std::os::unix::io::AsRawFd;
-use std::thread;
+use std::process::ExitCode;
 use std::time::{Duration, Instant};
 
-extern crate libc;
-
-/// Poll interval for system metric collection (default 3000ms, configurable via first argument in milliseconds)
-fn get_poll_interval() -> Duration {
-    let millis = env::args()
-        .nth(1)
-        .and_then(|arg| arg.parse::<u64>().ok())
-        .unwrap_or(3000);
-    Duration::from_millis(millis)
-}
-/// Path to /proc/stat for CPU metrics
-const PROC_STAT_PATH: &str = "/proc/stat";
-/// Path to /proc/meminfo for memory metrics
-const MEMINFO_PATH: &str = "/proc/meminfo";
-/// Path to /proc/net/dev for network metrics
-const NET_DEV_PATH: &str = "/proc/net/dev";
-/// Path to /proc/diskstats for disk metrics
-const DISKSTATS_PATH: &str = "/proc/diskstats";
-/// Initial capacity for JSON payload buffer
-const PAYLOAD_CAPACITY: usize = 4096;
-/// Reference bandwidth for network level calculation (125 Mbps)
-const NET_REF_BPS: f64 = 125_000_000.0;
-/// Reference bandwidth for disk level calculation (600 Mbps)
-const DISK_REF_BPS: f64 = 600_000_000.0;
-/// Disk sector size in bytes
-const DISK_SECTOR_SIZE: u64 = 512;
-/// Minimum elapsed time to avoid division by zero
-const MIN_ELAPSED: f64 = 1e-8;
-
-#[derive(Clone, Copy)]
-/// CPU counter values from /proc/stat (user, nice, system, idle, etc.)
-struct CpuCounters {
-    /// Total ticks (sum of all modes)
-    total: u64,
-    /// Idle ticks
-    idle: u64,
-}
-
-#[derive(Clone, Copy)]
-/// Network interface counter values
-struct NetCounters {
-    /// Bytes received
-    rx: u64,
-    /// Bytes transmitted
-    tx: u64,
-}
-
-#[derive(Clone, Copy)]
-/// Disk counter values
-struct DiskCounters {
-    /// Bytes read
-    read: u64,
-    /// Bytes written
-    write: u64,
+use polling_server::adaptive::{self, Adaptive};
+use polling_server::cli::{self, Command, Options};
+use polling_server::compositor::Backend;
+use polling_server::config::{self, Config, DEFAULT_INTERVAL};
+use polling_server::cpu::{cpu_indices, PROC_STAT_PATH};
+use polling_server::event_loop::EventLoop;
+use polling_server::output::{Format, Stdout};
+use polling_server::pulse::{self, PulseSource};
+use polling_server::signals::SignalSource;
+use polling_server::source::Roots;
+use polling_server::uevent::UeventSource;
+use polling_server::{battery, build_payload, profile, suspend};
+use polling_server::{Event, EventState, Registry, PAYLOAD_CAPACITY};
+
+/// How long `once` measures rates and waits for desktop state
+const ONCE_WINDOW: Duration = Duration::from_secs(1);
+/// How long the final payload waits for a slow reader on shutdown
+const SHUTDOWN_DRAIN: Duration = Duration::from_secs(1);
+
+fn main() -> ExitCode {
+    let result = cli::parse(env::args().skip(1))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+        .and_then(execute);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("polling-server: {}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-/// CPU metric entry for output
-struct CpuEntry {
-    /// CPU identifier (e.g., "cpu0", "cpu1")
-    id: String,
-    /// Usage percentage (0-100)
-    usage: u32,
+fn execute(command: Command) -> io::Result<()> {
+    match command {
+        Command::Run(options) => run_command(&options),
+        Command::Once(options) => once_command(&options),
+        Command::ListCollectors => list_collectors_command(),
+        Command::CheckConfig(options) => check_config_command(&options),
+        Command::Completions(_) => {
+            print!("{}", cli::fish_completions(&Registry::with_defaults()));
+            Ok(())
+        }
+        Command::Layout(target) => layout_command(target),
+        Command::Workspace(target) => workspace_command(target),
+        Command::Profile(target, options) => profile_command(target, &options.roots()),
+        Command::Thresholds(start, end, options) => thresholds_command(start, end, &options.roots()),
+        Command::Help => {
+            print!("{}", cli::help());
+            Ok(())
+        }
+    }
 }
 
-/// Memory metric entry for output
-struct MemoryEntry {
-    /// Total memory in KiB
-    total_kib: u64,
-    /// Available memory in KiB
-    available_kib: u64,
-    /// Used percentage (0-100.0)
-    used_percent: f64,
+/// Built-in collectors and the config file with `options` applied on top.
+/// A missing default config yields the defaults; a missing `--config` is an error.
+fn load_config(options: &Options) -> io::Result<(Registry, Config)> {
+    let registry = Registry::with_defaults();
+    let mut config = match (&options.config, &config::default_path()) {
+        (Some(path), _) if !path.exists() => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: config file not found", path.display()),
+            ))
+        }
+        (Some(path), _) | (None, Some(path)) => Config::load(path, &registry)?,
+        (None, None) => Config::default(),
+    };
+    options
+        .apply(&mut config, &registry)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    Ok((registry, config))
 }
 
-/// Network interface entry for output
-struct NetworkEntry {
-    /// Interface name
-    iface: String,
-    /// TX level (0-10)
-    tx_level: u8,
-    /// RX level (0-10)
-    rx_level: u8,
-    /// TX rate in MiB/s
-    tx_mib_s: f64,
-    /// RX rate in MiB/s
-    rx_mib_s: f64,
+/// Add the event-driven sources to `event_loop`, pushing their initial state to
+/// `updates`. A source that can't connect is reported and left out.
+fn add_sources(event_loop: &mut EventLoop, updates: &mut Vec<Event>) {
+    if let Some(backend) = Backend::detect() {
+        if let Err(err) = backend.connect().and_then(|source| event_loop.add(source, updates)) {
+            eprintln!("polling-server: {}: {}", backend.name(), err);
+        }
+    }
+    if let Some(path) = pulse::socket_path() {
+        let added = PulseSource::new(path, pulse::read_cookie())
+            .and_then(|source| event_loop.add(Box::new(source), updates));
+        if let Err(err) = added {
+            eprintln!("polling-server: pulse: {}", err);
+        }
+    }
 }
 
-/// Disk device entry for output
-struct DiskEntry {
-    /// Device name
-    device: String,
-    /// Read level (0-10)
-    read_level: u8,
-    /// Write level (0-10)
-    write_level: u8,
-    /// Read rate in MiB/s
-    read_mib_s: f64,
-    /// Write rate in MiB/s
-    write_mib_s: f64,
-}
+/// Handle `polling-server [run]`: stream payloads until stdout is closed or a
+/// termination signal arrives; SIGHUP reloads the config.
+fn run_command(options: &Options) -> io::Result<()> {
+    // Collectors open their files ONCE here and reuse them with pread() every poll
+    let (mut registry, mut config) = load_config(options)?;
+    let roots = options.roots();
+    registry.configure(&config);
+    registry.init(&roots);
 
-fn main() -> io::Result<()> {
-    let poll_interval = get_poll_interval();
-    
-    // Use Vec instead of HashMap for CPU cores - O(1) lookup instead of O(hash)
-    // Max 256 cores, usually ~16. Much faster than String-keyed HashMap
-    let mut cpu_prev: Vec<Option<CpuCounters>> = vec![None; 256];
-    let mut net_prev: HashMap<&'static str, NetCounters> = HashMap::with_capacity(16);
-    let mut disk_prev: HashMap<&'static str, DiskCounters> = HashMap::with_capacity(16);
     let mut payload = String::with_capacity(PAYLOAD_CAPACITY);
-    let mut cpu_entries = Vec::with_capacity(256);
-    let mut net_entries = Vec::with_capacity(16);
-    let mut disk_entries = Vec::with_capacity(16);
-    let mut last_instant = Instant::now();
-    
-    // Pre-allocate read buffers - just enough for actual /proc file sizes
-    // /proc/stat: ~5.5KB, /proc/meminfo: ~1.6KB, /proc/net/dev: ~1KB, /proc/diskstats: ~300B
-    let mut stat_buf = vec![0u8; 8192];
-    let mut meminfo_buf = vec![0u8; 4096];
-    let mut net_buf = vec![0u8; 4096];
-    let mut disk_buf = vec![0u8; 4096];
-
-    // Open files ONCE at startup, reuse with pread() - avoids repeated open() syscalls
-    let stat_file = File::open(PROC_STAT_PATH)?;
-    let meminfo_file = File::open(MEMINFO_PATH)?;
-    let net_file = File::open(NET_DEV_PATH)?;
-    let disk_file = File::open(DISKSTATS_PATH)?;
-
-    let stat_fd = stat_file.as_raw_fd();
-    let meminfo_fd = meminfo_file.as_raw_fd();
-    let net_fd = net_file.as_raw_fd();
-    let disk_fd = disk_file.as_raw_fd();
+    // Last payload written; identical payloads are not written again
+    let mut last = String::with_capacity(PAYLOAD_CAPACITY);
+    let mut line = String::with_capacity(PAYLOAD_CAPACITY);
+    let mut last_suspended = suspend::suspended_secs();
+
+    // One thread waits on the schedule timer and every event source
+    let mut event_loop = EventLoop::new()?;
+    let mut updates = Vec::new();
+    event_loop.add(Box::new(SignalSource::new()?), &mut updates)?;
+    let mut uevent_subsystems = Vec::new();
+    listen_uevents(&registry, &mut uevent_subsystems, &mut event_loop);
+    add_sources(&mut event_loop, &mut updates);
+    let mut state = EventState::default();
+    let mut adaptive = Adaptive::default();
+    // Signals are only read between writes, so a write must never block
+    let mut stdout = Stdout::non_blocking()?;
 
     loop {
         let loop_start = Instant::now();
-        let elapsed = loop_start.duration_since(last_instant).as_secs_f64();
-        last_instant = loop_start;
-
-        cpu_entries.clear();
-        let stat_len = pread_file(stat_fd, &mut stat_buf)?;
-        collect_cpu(&stat_buf[..stat_len], &mut cpu_prev, &mut cpu_entries);
-        
-        let meminfo_len = pread_file(meminfo_fd, &mut meminfo_buf)?;
-        let memory = collect_memory(&meminfo_buf[..meminfo_len]);
-        
-        net_entries.clear();
-        let net_len = pread_file(net_fd, &mut net_buf)?;
-        collect_network(elapsed, &net_buf[..net_len], &mut net_prev, &mut net_entries);
-        net_entries.sort_by(|a, b| a.iface.cmp(&b.iface));
-        
-        disk_entries.clear();
-        let disk_len = pread_file(disk_fd, &mut disk_buf)?;
-        collect_disks(elapsed, &disk_buf[..disk_len], &mut disk_prev, &mut disk_entries);
-        disk_entries.sort_by(|a, b| a.device.cmp(&b.device));
-
-        build_payload(&mut payload, &cpu_entries, memory.as_ref(), &net_entries, &disk_entries);
-
-        if let Err(err) = write_payload(&payload) {
-            if err.kind() == io::ErrorKind::BrokenPipe {
-                break;
-            }
-            return Err(err);
-        }
 
-        let loop_duration = loop_start.elapsed();
-        if loop_duration < poll_interval {
-            thread::sleep(poll_interval - loop_duration);
+        // Instant does not advance during suspend while the counters do, so the
+        // first sample after resume would divide a long delta by a short elapsed
+        let suspended = suspend::suspended_secs();
+        if suspended - last_suspended >= suspend::MIN_SLEEP_SECS {
+            registry.reset_baselines();
         }
-    }
-    
-    Ok(())
-}
+        last_suspended = suspended;
 
-/// Read file contents using pread64 syscall with no file pointer changes.
-/// This avoids repeated open/close syscalls by reusing file descriptors.
-///
-/// # Arguments
-/// * `fd` - Open file descriptor (must be kept open by caller)
-/// * `buf` - Buffer to read into (sized appropriately)
-///
-/// # Returns
-/// Number of bytes read, or io::Error on failure
-#[inline]
-fn pread_file(fd: i32, buf: &mut [u8]) -> io::Result<usize> {
-    // Direct libc::pread64 - zero overhead wrapper
-    let bytes_read = unsafe {
-        libc::pread64(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
-    };
-    
-    if bytes_read < 0 {
-        Err(io::Error::last_os_error())
-    } else {
-        Ok(bytes_read as usize)
-    }
-}
+        // A collector that failed to come up (e.g. sysfs not ready at boot) is
+        // retried with backoff, and listens for its uevents once it is up
+        if registry.retry_init(&roots, loop_start) {
+            listen_uevents(&registry, &mut uevent_subsystems, &mut event_loop);
+        }
 
-/// Parse CPU statistics from /proc/stat and calculate usage percentages.
-/// Uses Vec-based O(1) storage indexed by CPU number for fast lookups.
-#[inline]
-fn collect_cpu(
-    data: &[u8],
-    prev: &mut [Option<CpuCounters>],
-    entries: &mut Vec<CpuEntry>,
-) {
-    let mut line_start = 0;
-    
-    for (i, &byte) in data.iter().enumerate() {
-        if byte == b'\n' || i == data.len() - 1 {
-            let end = if byte == b'\n' { i } else { i + 1 };
-            let line = &data[line_start..end];
-            
-            if !line.starts_with(b"cpu") {
-                line_start = i + 1;
-                continue;
-            }
-            
-            if line.len() < 5 || !line[3].is_ascii_digit() {
-                line_start = i + 1;
-                continue;
-            }
-            
-            // Extract cpu number - cpu0, cpu1, etc.
-            // Fast path: parse as u8 directly
-            let mut cpu_idx = 0usize;
-            let mut pos = 3;
-            while pos < line.len() && line[pos].is_ascii_digit() {
-                cpu_idx = cpu_idx * 10 + (line[pos] - b'0') as usize;
-                pos += 1;
-            }
-            
-            if cpu_idx >= 256 {
-                line_start = i + 1;
-                continue;
-            }
-            
-            // Skip to first space
-            while pos < line.len() && line[pos] != b' ' && line[pos] != b'\t' {
-                pos += 1;
-            }
-            
-            // Parse numbers
-            let mut total: u64 = 0;
-            let mut idle: u64 = 0;
-            let mut field = 0;
-            let mut num = 0u64;
-            let mut in_num = false;
-            
-            for &b in &line[pos..] {
-                if b.is_ascii_digit() {
-                    num = num.wrapping_mul(10).wrapping_add((b - b'0') as u64);
-                    in_num = true;
-                } else if in_num {
-                    total += num;
-                    if field == 3 {
-                        idle = num;
+        // Only the collectors whose period has elapsed are sampled; the payload
+        // goes out when any section changed
+        registry.sample(loop_start);
+        adapt(&mut adaptive, &mut registry, &config, loop_start);
+
+        // Wait for the next collector, re-emitting the payload whenever an event changes it
+        let interval = config.interval.unwrap_or(DEFAULT_INTERVAL);
+        let deadline = registry.next_due().unwrap_or(loop_start + interval);
+        loop {
+            // Bursts (e.g. snapshots after connect) arrive together and make one payload
+            let mut reload = false;
+            let mut rescheduled = false;
+            for event in updates.drain(..) {
+                match event {
+                    Event::Reload => reload = true,
+                    Event::Uevent(subsystem) => {
+                        let now = Instant::now();
+                        // e.g. plugging in the charger brings back the full rate
+                        if registry.sample_uevent(subsystem, now) {
+                            rescheduled |= adapt(&mut adaptive, &mut registry, &config, now);
+                        }
                     }
-                    if field > 8 {
-                        break;
+                    Event::Shutdown => {
+                        // Final payload with the latest state, after the rest of a line
+                        // cut short; a stalled reader only holds up the exit briefly
+                        build_payload(&mut payload, &registry, &state);
+                        let bytes = render(&payload, config.format, &mut line);
+                        return match stdout.write_final(bytes, SHUTDOWN_DRAIN) {
+                            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err),
+                            _ => Ok(()),
+                        };
                     }
-                    field += 1;
-                    num = 0;
-                    in_num = false;
+                    event => state.apply(event, config.title_width),
                 }
             }
-            
-            // O(1) lookup instead of O(hash) HashMap lookup
-            let usage = if let Some(prev_sample) = prev[cpu_idx] {
-                let total_diff = total.saturating_sub(prev_sample.total);
-                if total_diff == 0 {
-                    0
-                } else {
-                    let idle_diff = idle.saturating_sub(prev_sample.idle);
-                    let active = total_diff.saturating_sub(idle_diff);
-                    (100 * active / total_diff) as u32
-                }
-            } else {
-                0
-            };
-            
-            prev[cpu_idx] = Some(CpuCounters { total, idle });
-            
-            // Build cpu ID string manually without format! macro overhead
-            let mut cpu_id = String::with_capacity(8);
-            cpu_id.push_str("cpu");
-            itoa_usize(&mut cpu_id, cpu_idx);
-            
-            entries.push(CpuEntry { id: cpu_id, usage });
-            
-            line_start = i + 1;
-        }
-    }
-}
 
-/// Parse memory statistics from /proc/meminfo.
-#[inline]
-fn collect_memory(data: &[u8]) -> Option<MemoryEntry> {
-    let mut total_kib = 0u64;
-    let mut available_kib = 0u64;
-    let mut found_both = 0u8;
-    
-    let mut line_start = 0;
-    for (i, &byte) in data.iter().enumerate() {
-        if byte == b'\n' || i == data.len() - 1 {
-            let end = if byte == b'\n' { i } else { i + 1 };
-            let line = &data[line_start..end];
-            
-            if line.starts_with(b"MemTotal:") && found_both & 1 == 0 {
-                total_kib = parse_number_from_line(line);
-                found_both |= 1;
-            } else if line.starts_with(b"MemAvailable:") && found_both & 2 == 0 {
-                available_kib = parse_number_from_line(line);
-                found_both |= 2;
-                if found_both == 3 {
-                    break;
+            if reload {
+                // A broken config keeps the running one
+                match load_config(options) {
+                    Ok((next, next_config)) => {
+                        registry.reconfigure(next, &next_config);
+                        registry.init(&roots);
+                        listen_uevents(&registry, &mut uevent_subsystems, &mut event_loop);
+                        config = next_config;
+                    }
+                    Err(err) => eprintln!("polling-server: reload failed, keeping the current config: {}", err),
                 }
+                // Sample right away so added collectors show up without waiting a full interval
+                break;
             }
-            
-            line_start = i + 1;
-        }
-    }
-    
-    if total_kib == 0 {
-        return None;
-    }
-    
-    let used_kib = total_kib.saturating_sub(available_kib);
-    let used_percent = (used_kib as f64 * 100.0) / total_kib as f64;
-    
-    Some(MemoryEntry {
-        total_kib,
-        available_kib,
-        used_percent,
-    })
-}
-
-#[inline]
-fn parse_number_from_line(line: &[u8]) -> u64 {
-    let mut num = 0u64;
-    let mut in_num = false;
-    for &byte in line {
-        if byte.is_ascii_digit() {
-            num = num.wrapping_mul(10).wrapping_add((byte - b'0') as u64);
-            in_num = true;
-        } else if in_num {
-            // Found first number, return it
-            return num;
-        }
-        // Skip non-digits until we find a number
-    }
-    num
-}
 
-/// Parse network interface counters from /proc/net/dev.
-/// Returns HashMap of interface names to byte counters.
-#[inline]
-fn parse_network(data: &[u8]) -> HashMap<&'static str, (u64, u64)> {
-    let mut result: HashMap<&'static str, (u64, u64)> = HashMap::with_capacity(16);
-    let mut line_start = 0;
-    let mut skip_count = 0;
-    
-    for (i, &byte) in data.iter().enumerate() {
-        if byte == b'\n' || i == data.len() - 1 {
-            let end = if byte == b'\n' { i } else { i + 1 };
-            let line = &data[line_start..end];
-            
-            if skip_count < 2 {
-                skip_count += 1;
-                line_start = i + 1;
-                continue;
-            }
-            
-            // Find colon
-            let colon_pos = match line.iter().position(|&b| b == b':') {
-                Some(p) => p,
-                None => {
-                    line_start = i + 1;
-                    continue;
-                }
-            };
-            
-            let iface_bytes = &line[..colon_pos];
-            let iface = std::str::from_utf8(iface_bytes).unwrap_or("").trim();
-            
-            if iface.is_empty() || iface.len() > 15 {
-                line_start = i + 1;
-                continue;
-            }
-            
-            // Skip certain interfaces
-            match iface.as_bytes().first() {
-                Some(&b'l') if iface == "lo" => {
-                    line_start = i + 1;
-                    continue;
-                },
-                Some(&b'd') if iface.starts_with("docker") => {
-                    line_start = i + 1;
-                    continue;
-                },
-                Some(&b'v') if iface.starts_with("veth") => {
-                    line_start = i + 1;
-                    continue;
-                },
-                _ => {}
-            }
-            
-            // Parse numbers after colon
-            let mut rx_bytes: u64 = 0;
-            let mut tx_bytes: u64 = 0;
-            let mut field = 0;
-            let mut num = 0u64;
-            let mut in_num = false;
-            
-            for &b in &line[colon_pos + 1..] {
-                if b.is_ascii_digit() {
-                    num = num.wrapping_mul(10).wrapping_add((b - b'0') as u64);
-                    in_num = true;
-                } else if in_num {
-                    if field == 0 {
-                        rx_bytes = num;
-                    } else if field == 8 {
-                        tx_bytes = num;
-                    }
-                    field += 1;
-                    num = 0;
-                    in_num = false;
-                    if field > 8 {
-                        break;
+            // A stalled reader gets the latest payload once it catches up, not a backlog
+            if !(config.adaptive && adaptive::stdout_blocked()) {
+                build_payload(&mut payload, &registry, &state);
+                match emit_changed(&mut stdout, &mut payload, &mut last, config.format, &mut line)? {
+                    None => return Ok(()),
+                    // Device events are emitted once, not with every event-driven re-emit;
+                    // compare the next payload against this one without them so their
+                    // removal alone doesn't count as a change
+                    Some(true) => {
+                        if registry.clear_device_events() {
+                            build_payload(&mut last, &registry, &state);
+                        }
                     }
+                    // Dropped on a full pipe; the events are kept and go out with the next one
+                    Some(false) => {}
                 }
             }
-            if in_num && field == 8 {
-                tx_bytes = num;
+            // Finish a line cut short as soon as the reader makes room
+            event_loop.watch_writable(stdout.as_raw_fd(), stdout.has_pending())?;
+            if rescheduled {
+                break;
+            }
+
+            if event_loop.wait(deadline, &mut updates)? {
+                break;
             }
-            
-            let iface_static = Box::leak(iface.to_string().into_boxed_str());
-            result.insert(iface_static, (rx_bytes, tx_bytes));
-            
-            line_start = i + 1;
         }
     }
-    result
 }
 
-/// Calculate network throughput rates and populate entries.
-/// Requires previous counters for rate calculation.
-#[inline]
-fn calculate_network_rates(
-    elapsed: f64,
-    parsed: HashMap<&'static str, (u64, u64)>,
-    prev: &mut HashMap<&'static str, NetCounters>,
-    entries: &mut Vec<NetworkEntry>,
-) {
-    let elapsed = elapsed.max(MIN_ELAPSED);
-    
-    for (iface, (rx_bytes, tx_bytes)) in parsed {
-        let counters = prev
-            .entry(iface)
-            .or_insert(NetCounters { rx: rx_bytes, tx: tx_bytes });
-        
-        let rx_rate = if rx_bytes >= counters.rx {
-            (rx_bytes - counters.rx) as f64 / elapsed
-        } else {
-            0.0
-        };
-        let tx_rate = if tx_bytes >= counters.tx {
-            (tx_bytes - counters.tx) as f64 / elapsed
-        } else {
-            0.0
-        };
-        
-        counters.rx = rx_bytes;
-        counters.tx = tx_bytes;
-        
-        entries.push(NetworkEntry {
-            iface: iface.to_string(),
-            tx_level: rate_to_level(tx_rate, NET_REF_BPS),
-            rx_level: rate_to_level(rx_rate, NET_REF_BPS),
-            tx_mib_s: tx_rate / 1_048_576.0,
-            rx_mib_s: rx_rate / 1_048_576.0,
-        });
-    }
+/// Pick the interval multiplier for adaptive polling, 1 when it is off.
+/// Returns whether it changed, which moves the schedule.
+fn adapt(adaptive: &mut Adaptive, registry: &mut Registry, config: &Config, now: Instant) -> bool {
+    let factor = if config.adaptive {
+        adaptive.slowdown(now, registry.activity(), registry.on_battery(), adaptive::stdout_blocked())
+    } else {
+        1
+    };
+    registry.set_slowdown(factor, now)
 }
 
-/// Collect network statistics: parse and calculate rates.
-/// Wrapper for convenience - calls parse_network and calculate_network_rates.
-#[inline]
-fn collect_network(
-    elapsed: f64,
-    data: &[u8],
-    prev: &mut HashMap<&'static str, NetCounters>,
-    entries: &mut Vec<NetworkEntry>,
-) {
-    let parsed = parse_network(data);
-    calculate_network_rates(elapsed, parsed, prev, entries);
+/// Listen for the uevent subsystems of `registry` not yet in `listening`.
+/// Collectors still get their periodic samples when the socket can't be opened.
+fn listen_uevents(registry: &Registry, listening: &mut Vec<&'static str>, event_loop: &mut EventLoop) {
+    let missing: Vec<&'static str> = registry
+        .uevent_subsystems()
+        .into_iter()
+        .filter(|subsystem| !listening.contains(subsystem))
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+    let added = UeventSource::new(missing.clone()).and_then(|source| event_loop.add(Box::new(source), &mut Vec::new()));
+    match added {
+        Ok(()) => listening.extend(missing),
+        Err(err) => eprintln!("polling-server: kernel uevents unavailable: {}", err),
+    }
 }
 
-/// Parse disk I/O counters from /proc/diskstats.
-/// Returns HashMap of device names to (read_sectors, write_sectors).
-#[inline]
-fn parse_disks(data: &[u8]) -> HashMap<&'static str, (u64, u64)> {
-    let mut result: HashMap<&'static str, (u64, u64)> = HashMap::with_capacity(16);
-    
-    let mut line_start = 0;
-    
-    for (i, &byte) in data.iter().enumerate() {
-        if byte == b'\n' || i == data.len() - 1 {
-            let end = if byte == b'\n' { i } else { i + 1 };
-            let line = &data[line_start..end];
-            
-            // Parse fields: skip first two, then name, then fields
-            let mut field = 0;
-            let mut num = 0u64;
-            let mut in_num = false;
-            let mut name_start = 0;
-            let mut name_len = 0;
-            let mut read_sectors: u64 = 0;
-            let mut write_sectors: u64 = 0;
-            
-            for (j, &b) in line.iter().enumerate() {
-                if b.is_ascii_digit() {
-                    if !in_num && field == 2 {
-                        name_start = j;
-                    }
-                    num = num.wrapping_mul(10).wrapping_add((b - b'0') as u64);
-                    in_num = true;
-                } else if in_num {
-                    match field {
-                        2 => {
-                            name_len = j - name_start;
-                        },
-                        5 => read_sectors = num,
-                        9 => {
-                            write_sectors = num;
-                            break;
-                        },
-                        _ => {}
-                    }
-                    field += 1;
-                    num = 0;
-                    in_num = false;
-                }
-            }
-            
-            if field < 9 && in_num {
-                if field == 9 {
-                    write_sectors = num;
-                } else if field == 5 {
-                    read_sectors = num;
-                }
-            }
-            
-            if name_len == 0 {
-                line_start = i + 1;
-                continue;
-            }
-            
-            let name_bytes = &line[name_start..name_start + name_len];
-            let name = std::str::from_utf8(name_bytes).unwrap_or("");
-            let name_bytes = name.as_bytes();
-            let last_byte = *name_bytes.last().unwrap_or(&0);
-            
-            // Skip pseudo-devices
-            match name.as_bytes().first() {
-                Some(&b'l') if name.starts_with("loop") => {
-                    line_start = i + 1;
-                    continue;
-                },
-                Some(&b'r') if name.starts_with("ram") => {
-                    line_start = i + 1;
-                    continue;
-                },
-                Some(&b'd') if name.starts_with("dm-") => {
-                    line_start = i + 1;
-                    continue;
-                },
-                _ => {}
-            }
-            
-            // Skip partitions (ends with digit and contains p or starts with s/h/v)
-            if last_byte.is_ascii_digit() && 
-               (name.contains('p') || matches!(name.as_bytes().first(), Some(&b's') | Some(&b'h') | Some(&b'v'))) {
-                line_start = i + 1;
-                continue;
-            }
-            
-            let name_static = Box::leak(name.to_string().into_boxed_str());
-            result.insert(name_static, (read_sectors, write_sectors));
-            
-            line_start = i + 1;
+/// Handle `polling-server once`: sample twice over `ONCE_WINDOW` so rates are
+/// meaningful, print that payload and exit.
+fn once_command(options: &Options) -> io::Result<()> {
+    let (mut registry, config) = load_config(options)?;
+    registry.configure(&config);
+    registry.init(&options.roots());
+    registry.sample_all(Instant::now());
+
+    // Desktop state arriving while the baseline ages is included
+    let mut event_loop = EventLoop::new()?;
+    let mut updates = Vec::new();
+    add_sources(&mut event_loop, &mut updates);
+    let mut state = EventState::default();
+    let deadline = Instant::now() + ONCE_WINDOW;
+    loop {
+        for event in updates.drain(..) {
+            state.apply(event, config.title_width);
         }
+        if event_loop.wait(deadline, &mut updates)? {
+            break;
+        }
+    }
+    for event in updates.drain(..) {
+        state.apply(event, config.title_width);
     }
-    result
+
+    registry.sample_all(Instant::now());
+    let mut payload = String::with_capacity(PAYLOAD_CAPACITY);
+    build_payload(&mut payload, &registry, &state);
+    let mut line = String::new();
+    write_payload(&payload, config.format, &mut line)
 }
 
-/// Calculate disk I/O throughput rates and populate entries.
-/// Requires previous counters for rate calculation.
-#[inline]
-fn calculate_disk_rates(
-    elapsed: f64,
-    parsed: HashMap<&'static str, (u64, u64)>,
-    prev: &mut HashMap<&'static str, DiskCounters>,
-    entries: &mut Vec<DiskEntry>,
-) {
-    let elapsed = elapsed.max(MIN_ELAPSED);
-    
-    for (name, (read_sectors, write_sectors)) in parsed {
-        let counters = prev
-            .entry(name)
-            .or_insert(DiskCounters {
-                read: read_sectors,
-                write: write_sectors,
-            });
-        
-        let read_rate = if read_sectors >= counters.read {
-            (read_sectors - counters.read) as f64 * DISK_SECTOR_SIZE as f64 / elapsed
-        } else {
-            0.0
-        };
-        let write_rate = if write_sectors >= counters.write {
-            (write_sectors - counters.write) as f64 * DISK_SECTOR_SIZE as f64 / elapsed
-        } else {
-            0.0
-        };
-        
-        counters.read = read_sectors;
-        counters.write = write_sectors;
-        
-        entries.push(DiskEntry {
-            device: name.to_string(),
-            read_level: rate_to_level(read_rate, DISK_REF_BPS),
-            write_level: rate_to_level(write_rate, DISK_REF_BPS),
-            read_mib_s: read_rate / 1_048_576.0,
-            write_mib_s: write_rate / 1_048_576.0,
-        });
+/// Handle `polling-server list-collectors`: print name, payload key and extra options.
+fn list_collectors_command() -> io::Result<()> {
+    let registry = Registry::with_defaults();
+    let mut stdout = io::stdout().lock();
+    for (name, key) in registry.names().zip(registry.keys()) {
+        let options = registry.options(name).unwrap_or_default();
+        let line = format!("{:<10} {:<3} {}", name, key, options.join(", "));
+        writeln!(stdout, "{}", line.trim_end())?;
     }
+    Ok(())
 }
 
-/// Collect disk statistics: parse and calculate rates.
-/// Wrapper for convenience - calls parse_disks and calculate_disk_rates.
-#[inline]
-fn collect_disks(
-    elapsed: f64,
-    data: &[u8],
-    prev: &mut HashMap<&'static str, DiskCounters>,
-    entries: &mut Vec<DiskEntry>,
-) {
-    let parsed = parse_disks(data);
-    calculate_disk_rates(elapsed, parsed, prev, entries);
+/// Handle `polling-server check-config`: validate the config and report where it was read from.
+fn check_config_command(options: &Options) -> io::Result<()> {
+    let path = options.config.clone().or_else(config::default_path);
+    load_config(options)?;
+    match path {
+        Some(path) if path.exists() => println!("{}: ok", path.display()),
+        Some(path) => println!("{}: not found, using defaults", path.display()),
+        None => println!("no config path ($XDG_CONFIG_HOME and $HOME unset), using defaults"),
+    }
+    Ok(())
 }
 
-// Extreme optimization: inline number-to-string conversions
-#[inline]
-fn itoa_u8(s: &mut String, mut n: u8) {
-    if n == 0 {
-        s.push('0');
-        return;
+/// Handle `polling-server layout [next|prev|<index>]`: switch keyboard layout and exit.
+fn layout_command(target: Option<String>) -> io::Result<()> {
+    let target = target.unwrap_or_else(|| "next".to_string());
+    if !matches!(target.as_str(), "next" | "prev") && target.parse::<u32>().is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown layout target '{}', expected next, prev or an index", target),
+        ));
     }
-    let mut buf = [b'0'; 3];
-    let mut i = 3;
-    while n > 0 {
-        i -= 1;
-        buf[i] = b'0' + (n % 10);
-        n /= 10;
-    }
-    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
+    detect_backend()?.switch_layout(&target)
 }
 
-#[inline]
-fn itoa_usize(s: &mut String, mut n: usize) {
-    if n == 0 {
-        s.push('0');
-        return;
-    }
-    let mut buf = [b'0'; 20];
-    let mut i = 20;
-    while n > 0 {
-        i -= 1;
-        buf[i] = b'0' + (n % 10) as u8;
-        n /= 10;
-    }
-    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
+/// Handle `polling-server workspace <number>`: focus a workspace and exit.
+fn workspace_command(target: Option<String>) -> io::Result<()> {
+    let id = target.as_deref().and_then(|t| t.parse::<i64>().ok()).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "expected a workspace number")
+    })?;
+    detect_backend()?.focus_workspace(id)
 }
 
-#[inline]
-fn itoa_u32(s: &mut String, mut n: u32) {
-    if n == 0 {
-        s.push('0');
-        return;
-    }
-    let mut buf = [b'0'; 10];
-    let mut i = 10;
-    while n > 0 {
-        i -= 1;
-        buf[i] = b'0' + (n % 10) as u8;
-        n /= 10;
-    }
-    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
+/// Handle `polling-server profile <name|next>`: switch power profile, governor and EPP, then exit.
+fn profile_command(target: Option<String>, roots: &Roots) -> io::Result<()> {
+    let target = target.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "expected a profile name or next")
+    })?;
+    let stat = std::fs::read(roots.resolve(PROC_STAT_PATH))?;
+    profile::set_profile(roots, &target, &cpu_indices(&stat))
 }
 
-#[inline]
-fn itoa_u64(s: &mut String, mut n: u64) {
-    if n == 0 {
-        s.push('0');
-        return;
+/// Handle `polling-server thresholds <start> <end>`: set battery charge thresholds and exit.
+fn thresholds_command(start: Option<String>, end: Option<String>, roots: &Roots) -> io::Result<()> {
+    let parse = |arg: Option<String>| arg.and_then(|a| a.parse::<u32>().ok());
+    match (parse(start), parse(end)) {
+        (Some(start), Some(end)) => battery::set_thresholds(roots, start, end),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected start and end percentages, e.g. thresholds 75 80",
+        )),
     }
-    let mut buf = [b'0'; 20];
-    let mut i = 20;
-    while n > 0 {
-        i -= 1;
-        buf[i] = b'0' + (n % 10) as u8;
-        n /= 10;
-    }
-    s.push_str(unsafe { std::str::from_utf8_unchecked(&buf[i..]) });
 }
 
-#[inline]
-fn ftoa_f64(s: &mut String, mut n: f64, prec: usize) {
-    if n < 0.0 {
-        s.push('-');
-        n = -n;
-    }
-    let int_part = n as u64;
-    itoa_u64(s, int_part);
-    s.push('.');
-    let mut frac = n - int_part as f64;
-    for _ in 0..prec {
-        frac *= 10.0;
-        let digit = frac as u8;
-        s.push((b'0' + digit) as char);
-        frac -= digit as f64;
-    }
+/// Backend for one-shot commands; unlike streaming, running outside a session is an error.
+fn detect_backend() -> io::Result<Backend> {
+    Backend::detect().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "neither a sway nor a Hyprland session was found")
+    })
 }
 
-/// Build JSON payload from collected metrics using optimized number formatting.
-/// Avoids format! macro overhead by using inlined itoa_* and ftoa_* functions.
+/// Write `payload` unless it equals `last`, then keep it as `last`. Whether it is
+/// current on stdout afterwards, `None` once the reader has gone away.
 #[inline]
-fn build_payload(
-    out: &mut String,
-    cpu: &[CpuEntry],
-    memory: Option<&MemoryEntry>,
-    network: &[NetworkEntry],
-    disks: &[DiskEntry],
-) {
-    out.clear();
-    out.reserve(PAYLOAD_CAPACITY);
-    
-    // Extreme optimization: pre-write static strings, use itoa for numbers
-    out.push_str("{\"c\":[");
-    for (idx, entry) in cpu.iter().enumerate() {
-        if idx > 0 {
-            out.push(',');
-        }
-        out.push_str("[\"");
-        out.push_str(&entry.id);
-        out.push_str("\",");
-        itoa_u32(out, entry.usage);
-        out.push(']');
+fn emit_changed(
+    stdout: &mut Stdout,
+    payload: &mut String,
+    last: &mut String,
+    format: Format,
+    line: &mut String,
+) -> io::Result<Option<bool>> {
+    // The rest of a line cut short goes out even when nothing changed since
+    match stdout.flush() {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(None),
+        result => result?,
     }
-    out.push_str("],\"m\":");
-    if let Some(mem) = memory {
-        out.push('[');
-        itoa_u64(out, mem.total_kib);
-        out.push(',');
-        itoa_u64(out, mem.available_kib);
-        out.push(',');
-        ftoa_f64(out, mem.used_percent, 1);
-        out.push(']');
-    } else {
-        out.push_str("null");
+    if payload == last {
+        return Ok(Some(true));
     }
-
-    out.push_str(",\"n\":[");
-    for (idx, entry) in network.iter().enumerate() {
-        if idx > 0 {
-            out.push(',');
-        }
-        out.push_str("[\"");
-        out.push_str(&entry.iface);
-        out.push_str("\",");
-        itoa_u8(out, entry.tx_level);
-        out.push(',');
-        itoa_u8(out, entry.rx_level);
-        out.push(',');
-        ftoa_f64(out, entry.tx_mib_s, 2);
-        out.push(',');
-        ftoa_f64(out, entry.rx_mib_s, 2);
-        out.push(']');
+    let taken = emit(stdout, payload, format, line)?;
+    // Dropped on a full pipe, `last` stays so the payload goes out next time
+    if taken == Some(true) {
+        mem::swap(payload, last);
     }
-    out.push_str("],\"d\":[");
-    for (idx, entry) in disks.iter().enumerate() {
-        if idx > 0 {
-            out.push(',');
-        }
-        out.push_str("[\"");
-        out.push_str(&entry.device);
-        out.push_str("\",");
-        itoa_u8(out, entry.read_level);
-        out.push(',');
-        itoa_u8(out, entry.write_level);
-        out.push(',');
-        ftoa_f64(out, entry.read_mib_s, 2);
-        out.push(',');
-        ftoa_f64(out, entry.write_mib_s, 2);
-        out.push(']');
+    Ok(taken)
+}
+
+/// Write a payload to the non-blocking stdout: whether the reader took it,
+/// `None` once the reader has gone away.
+#[inline]
+fn emit(stdout: &mut Stdout, payload: &str, format: Format, line: &mut String) -> io::Result<Option<bool>> {
+    match stdout.write_line(render(payload, format, line)) {
+        Ok(taken) => Ok(Some(taken)),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(None),
+        Err(err) => Err(err),
     }
-    out.push_str("]}");
 }
 
-/// Convert throughput rate to a 0-10 level indicator relative to reference.
+/// Payload in `format`, rendered into `line` unless it is the compact JSON as is.
 #[inline]
-fn rate_to_level(rate: f64, reference: f64) -> u8 {
-    if rate <= 0.0 || reference <= 0.0 {
-        return 0;
+fn render<'a>(payload: &'a str, format: Format, line: &'a mut String) -> &'a [u8] {
+    match format {
+        Format::Json => payload.as_bytes(),
+        _ => {
+            format.render(payload, line);
+            line.as_bytes()
+        }
     }
-    let ratio = (rate / reference).min(1.0);
-    let level = (ratio * 10.0).ceil() as u8;
-    level.min(10)
 }
 
-/// Write JSON payload to stdout with newline.
+/// Write JSON payload to stdout in `format` with newline, rendering into `line`.
 #[inline]
-fn write_payload(payload: &str) -> io::Result<()> {
+fn write_payload(payload: &str, format: Format, line: &mut String) -> io::Result<()> {
     let mut stdout = io::stdout();
-    stdout.write_all(payload.as_bytes())?;
+    stdout.write_all(render(payload, format, line))?;
     stdout.write_all(b"\n")?;
     stdout.flush()
 }
@@ -0,0 +1,117 @@
+//! Memory usage from /proc/meminfo.
+
+use std::io;
+
+use crate::collector::Collector;
+use crate::source::{ProcSource, Roots};
+use crate::{ftoa_f64, itoa_u64, parse_number_from_line};
+
+/// Path to /proc/meminfo for memory metrics
+pub const MEMINFO_PATH: &str = "/proc/meminfo";
+
+/// Memory metric entry for output
+pub struct MemoryEntry {
+    /// Total memory in KiB
+    pub total_kib: u64,
+    /// Available memory in KiB
+    pub available_kib: u64,
+    /// Used percentage (0-100.0)
+    pub used_percent: f64,
+}
+
+/// Parse memory statistics from /proc/meminfo.
+#[inline]
+pub fn collect_memory(data: &[u8]) -> Option<MemoryEntry> {
+    let mut total_kib = 0u64;
+    let mut available_kib = 0u64;
+    let mut found_both = 0u8;
+    
+    let mut line_start = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'\n' || i == data.len() - 1 {
+            let end = if byte == b'\n' { i } else { i + 1 };
+            let line = &data[line_start..end];
+            
+            if line.starts_with(b"MemTotal:") && found_both & 1 == 0 {
+                total_kib = parse_number_from_line(line);
+                found_both |= 1;
+            } else if line.starts_with(b"MemAvailable:") && found_both & 2 == 0 {
+                available_kib = parse_number_from_line(line);
+                found_both |= 2;
+                if found_both == 3 {
+                    break;
+                }
+            }
+            
+            line_start = i + 1;
+        }
+    }
+    
+    if total_kib == 0 {
+        return None;
+    }
+    
+    let used_kib = total_kib.saturating_sub(available_kib);
+    let used_percent = (used_kib as f64 * 100.0) / total_kib as f64;
+    
+    Some(MemoryEntry {
+        total_kib,
+        available_kib,
+        used_percent,
+    })
+}
+
+/// Total, available and used memory ("m")
+pub struct MemoryCollector {
+    source: ProcSource,
+    entry: Option<MemoryEntry>,
+}
+
+impl Default for MemoryCollector {
+    fn default() -> Self {
+        MemoryCollector {
+            // /proc/meminfo: ~1.6KB
+            source: ProcSource::new(MEMINFO_PATH, 4096),
+            entry: None,
+        }
+    }
+}
+
+impl Collector for MemoryCollector {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn key(&self) -> &'static str {
+        "m"
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.source.relocate(roots);
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, _elapsed: f64) {
+        self.entry = self.source.read().and_then(collect_memory);
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        if let Some(mem) = &self.entry {
+            out.push('[');
+            itoa_u64(out, mem.total_kib);
+            out.push(',');
+            itoa_u64(out, mem.available_kib);
+            out.push(',');
+            ftoa_f64(out, mem.used_percent, 1);
+            out.push(']');
+        } else {
+            out.push_str("null");
+        }
+    }
+
+    fn error(&self) -> Option<&str> {
+        self.source.error()
+    }
+}
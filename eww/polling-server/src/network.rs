@@ -0,0 +1,333 @@
+//! Network throughput from /proc/net/dev.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::collector::{Activity, Collector};
+use crate::config::CollectorConfig;
+use crate::devices::{DeviceClass, DeviceEvent, DeviceFilter, DeviceRegistry};
+use crate::source::{ProcSource, Roots};
+use crate::{counter_delta, ftoa_f64, itoa_u8, pread_file, rate_to_level, MIN_ELAPSED};
+
+/// Path to /proc/net/dev for network metrics
+pub const NET_DEV_PATH: &str = "/proc/net/dev";
+/// Directory holding one entry per network interface
+pub const NET_SYSFS_PATH: &str = "/sys/class/net";
+/// Reference bandwidth for network level calculation (125 Mbps)
+pub const NET_REF_BPS: f64 = 125_000_000.0;
+/// Combined rate of all interfaces below which the network counts as idle (MiB/s)
+const IDLE_MIB_S: f64 = 0.05;
+
+/// Network interface counter values
+pub struct NetCounters {
+    /// Bytes received
+    rx: u64,
+    /// Bytes transmitted
+    tx: u64,
+    /// Open sysfs `ifindex` of the interface; reads fail once it is deleted,
+    /// which catches re-creation even when the new counters overtake the old ones
+    ifindex: Option<File>,
+}
+
+/// Network interface entry for output
+pub struct NetworkEntry {
+    /// Interface name
+    pub iface: Rc<str>,
+    /// TX level (0-10)
+    pub tx_level: u8,
+    /// RX level (0-10)
+    pub rx_level: u8,
+    /// TX rate in MiB/s
+    pub tx_mib_s: f64,
+    /// RX rate in MiB/s
+    pub rx_mib_s: f64,
+    /// Counters were reset (e.g., interface re-created); rates are 0 for this sample
+    pub reset: bool,
+}
+
+/// Parse network interface counters from /proc/net/dev.
+/// Returns (interface, rx_bytes, tx_bytes) with names borrowed from `data`.
+#[inline]
+pub fn parse_network(data: &[u8]) -> Vec<(&str, u64, u64)> {
+    let mut result: Vec<(&str, u64, u64)> = Vec::with_capacity(16);
+    let mut line_start = 0;
+    let mut skip_count = 0;
+    
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'\n' || i == data.len() - 1 {
+            let end = if byte == b'\n' { i } else { i + 1 };
+            let line = &data[line_start..end];
+            
+            if skip_count < 2 {
+                skip_count += 1;
+                line_start = i + 1;
+                continue;
+            }
+            
+            // Find colon
+            let colon_pos = match line.iter().position(|&b| b == b':') {
+                Some(p) => p,
+                None => {
+                    line_start = i + 1;
+                    continue;
+                }
+            };
+            
+            let iface_bytes = &line[..colon_pos];
+            let iface = std::str::from_utf8(iface_bytes).unwrap_or("").trim();
+            
+            if iface.is_empty() || iface.len() > 15 {
+                line_start = i + 1;
+                continue;
+            }
+            
+            // Skip certain interfaces
+            match iface.as_bytes().first() {
+                Some(&b'l') if iface == "lo" => {
+                    line_start = i + 1;
+                    continue;
+                },
+                Some(&b'd') if iface.starts_with("docker") => {
+                    line_start = i + 1;
+                    continue;
+                },
+                Some(&b'v') if iface.starts_with("veth") => {
+                    line_start = i + 1;
+                    continue;
+                },
+                _ => {}
+            }
+            
+            // Parse numbers after colon
+            let mut rx_bytes: u64 = 0;
+            let mut tx_bytes: u64 = 0;
+            let mut field = 0;
+            let mut num = 0u64;
+            let mut in_num = false;
+            
+            for &b in &line[colon_pos + 1..] {
+                if b.is_ascii_digit() {
+                    num = num.wrapping_mul(10).wrapping_add((b - b'0') as u64);
+                    in_num = true;
+                } else if in_num {
+                    if field == 0 {
+                        rx_bytes = num;
+                    } else if field == 8 {
+                        tx_bytes = num;
+                    }
+                    field += 1;
+                    num = 0;
+                    in_num = false;
+                    if field > 8 {
+                        break;
+                    }
+                }
+            }
+            if in_num && field == 8 {
+                tx_bytes = num;
+            }
+            
+            result.push((iface, rx_bytes, tx_bytes));
+            
+            line_start = i + 1;
+        }
+    }
+    result
+}
+
+/// Calculate network throughput rates and populate entries.
+/// Requires previous counters for rate calculation; levels are relative to `ref_bps`.
+/// `net_sysfs` is the /sys/class/net directory used to detect re-created interfaces.
+#[inline]
+pub fn calculate_network_rates(
+    elapsed: f64,
+    ref_bps: f64,
+    net_sysfs: &Path,
+    parsed: &[(&str, u64, u64)],
+    registry: &mut DeviceRegistry<NetCounters>,
+    entries: &mut Vec<NetworkEntry>,
+    events: &mut Vec<DeviceEvent>,
+) {
+    let elapsed = elapsed.max(MIN_ELAPSED);
+    
+    let mut buf = [0u8; 16];
+    
+    registry.begin();
+    for &(iface, rx_bytes, tx_bytes) in parsed {
+        let open_ifindex = || File::open(net_sysfs.join(iface).join("ifindex")).ok();
+        let (name, counters) = registry.counters(iface, events);
+        let counters = counters.get_or_insert_with(|| NetCounters {
+            rx: rx_bytes,
+            tx: tx_bytes,
+            ifindex: open_ifindex(),
+        });
+        
+        let recreated = counters
+            .ifindex
+            .as_ref()
+            .is_some_and(|file| pread_file(file.as_raw_fd(), &mut buf).is_err());
+        if recreated {
+            counters.ifindex = open_ifindex();
+        }
+        
+        // A reset of either direction invalidates both: the interface was re-created
+        let (rx_rate, tx_rate, reset) =
+            match (counter_delta(counters.rx, rx_bytes), counter_delta(counters.tx, tx_bytes)) {
+                (Some(rx), Some(tx)) if !recreated => (rx as f64 / elapsed, tx as f64 / elapsed, false),
+                _ => (0.0, 0.0, true),
+            };
+        
+        counters.rx = rx_bytes;
+        counters.tx = tx_bytes;
+        
+        entries.push(NetworkEntry {
+            iface: Rc::clone(name),
+            tx_level: rate_to_level(tx_rate, ref_bps),
+            rx_level: rate_to_level(rx_rate, ref_bps),
+            tx_mib_s: tx_rate / 1_048_576.0,
+            rx_mib_s: rx_rate / 1_048_576.0,
+            reset,
+        });
+    }
+    registry.finish(events);
+}
+
+/// Collect network statistics: parse and calculate rates.
+/// Wrapper for convenience - calls parse_network and calculate_network_rates.
+#[inline]
+pub fn collect_network(
+    elapsed: f64,
+    data: &[u8],
+    registry: &mut DeviceRegistry<NetCounters>,
+    entries: &mut Vec<NetworkEntry>,
+    events: &mut Vec<DeviceEvent>,
+) {
+    let parsed = parse_network(data);
+    calculate_network_rates(elapsed, NET_REF_BPS, Path::new(NET_SYSFS_PATH), &parsed, registry, entries, events);
+}
+
+/// Per-interface throughput ("n"), with interfaces coming and going reported as device events
+pub struct NetworkCollector {
+    source: ProcSource,
+    devices: DeviceRegistry<NetCounters>,
+    entries: Vec<NetworkEntry>,
+    events: Vec<DeviceEvent>,
+    /// Reference bandwidth for the levels, bytes/s
+    ref_bps: f64,
+    filter: DeviceFilter,
+    /// /sys/class/net under the configured root
+    net_sysfs: PathBuf,
+}
+
+impl Default for NetworkCollector {
+    fn default() -> Self {
+        NetworkCollector {
+            // /proc/net/dev: ~1KB
+            source: ProcSource::new(NET_DEV_PATH, 4096),
+            devices: DeviceRegistry::new(DeviceClass::Net),
+            entries: Vec::with_capacity(16),
+            events: Vec::new(),
+            ref_bps: NET_REF_BPS,
+            filter: DeviceFilter::default(),
+            net_sysfs: PathBuf::from(NET_SYSFS_PATH),
+        }
+    }
+}
+
+impl Collector for NetworkCollector {
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn key(&self) -> &'static str {
+        "n"
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.source.relocate(roots);
+        self.net_sysfs = roots.resolve(NET_SYSFS_PATH);
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, elapsed: f64) {
+        self.entries.clear();
+        match self.source.read() {
+            Some(data) => {
+                let mut parsed = parse_network(data);
+                parsed.retain(|&(name, _, _)| self.filter.matches(name));
+                calculate_network_rates(
+                    elapsed,
+                    self.ref_bps,
+                    &self.net_sysfs,
+                    &parsed,
+                    &mut self.devices,
+                    &mut self.entries,
+                    &mut self.events,
+                );
+            }
+            None => self.devices.reset_baselines(),
+        }
+        self.entries.sort_by(|a, b| a.iface.cmp(&b.iface));
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        out.push('[');
+        for (idx, entry) in self.entries.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str("[\"");
+            out.push_str(&entry.iface);
+            out.push_str("\",");
+            itoa_u8(out, entry.tx_level);
+            out.push(',');
+            itoa_u8(out, entry.rx_level);
+            out.push(',');
+            ftoa_f64(out, entry.tx_mib_s, 2);
+            out.push(',');
+            ftoa_f64(out, entry.rx_mib_s, 2);
+            out.push_str(if entry.reset { ",true]" } else { ",false]" });
+        }
+        out.push(']');
+    }
+
+    fn options(&self) -> &'static [&'static str] {
+        &["ref_bps", "include", "exclude"]
+    }
+
+    fn configure(&mut self, config: &CollectorConfig) {
+        self.ref_bps = config.ref_bps.unwrap_or(NET_REF_BPS);
+        self.filter = DeviceFilter::new(config.include.clone(), config.exclude.clone());
+    }
+
+    fn error(&self) -> Option<&str> {
+        self.source.error()
+    }
+
+    fn reset_baselines(&mut self) {
+        self.devices.reset_baselines();
+    }
+
+    fn device_events(&self) -> &[DeviceEvent] {
+        &self.events
+    }
+
+    fn clear_device_events(&mut self) {
+        self.events.clear();
+    }
+
+    /// Idle while the combined throughput stays low (background chatter only)
+    fn activity(&self) -> Activity {
+        let total: f64 = self.entries.iter().map(|entry| entry.rx_mib_s + entry.tx_mib_s).sum();
+        if total >= IDLE_MIB_S {
+            Activity::Busy
+        } else {
+            Activity::Idle
+        }
+    }
+}
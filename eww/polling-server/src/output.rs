@@ -0,0 +1,240 @@
+//! Output formats of the payload line and the non-blocking stdout it goes to.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+
+/// How each payload is written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One compact JSON object per line, as eww's `deflisten` expects
+    Json,
+    /// Indented JSON for reading in a terminal
+    Pretty,
+}
+
+impl Format {
+    /// Names accepted in the config
+    pub const NAMES: &'static [&'static str] = &["json", "pretty"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Format::Json),
+            "pretty" => Some(Format::Pretty),
+            _ => None,
+        }
+    }
+
+    /// Write `payload` (compact JSON) to `out` in this format, without the trailing newline.
+    #[inline]
+    pub fn render(self, payload: &str, out: &mut String) {
+        out.clear();
+        match self {
+            Format::Json => out.push_str(payload),
+            Format::Pretty => indent(payload, out),
+        }
+    }
+}
+
+/// Re-indent compact JSON with two spaces per level, keeping numbers and strings verbatim.
+/// Arrays of scalars stay on one line so per-device rows remain readable.
+fn indent(payload: &str, out: &mut String) {
+    let bytes = payload.as_bytes();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    // Whether each open container is broken over lines
+    let mut multiline: Vec<bool> = Vec::new();
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str("  ");
+        }
+    };
+
+    for (idx, ch) in payload.char_indices() {
+        if in_string {
+            out.push(ch);
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match ch {
+            '"' => {
+                in_string = true;
+                out.push(ch);
+            }
+            '{' | '[' => {
+                out.push(ch);
+                let broken = ch == '{' || contains_container(&bytes[idx + 1..]);
+                multiline.push(broken);
+                depth += 1;
+                let empty = matches!(bytes.get(idx + 1), Some(b'}' | b']'));
+                if broken && !empty {
+                    newline(out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                let broken = multiline.pop().unwrap_or(false);
+                let empty = idx > 0 && matches!(bytes[idx - 1], b'{' | b'[');
+                if broken && !empty {
+                    newline(out, depth);
+                }
+                out.push(ch);
+            }
+            ',' => {
+                out.push(',');
+                if multiline.last().copied().unwrap_or(false) {
+                    newline(out, depth);
+                } else {
+                    out.push(' ');
+                }
+            }
+            ':' => out.push_str(": "),
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// Whether the container starting right before `rest` directly holds another container.
+fn contains_container(rest: &[u8]) -> bool {
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in rest {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => return true,
+            b'}' | b']' => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// stdout switched to non-blocking while streaming, so a reader that stops
+/// consuming (e.g., eww is stopped) can't hang the event loop and with it
+/// SIGTERM. The flags are restored on drop: the file description is shared,
+/// e.g. with the shell of a terminal.
+pub struct Stdout {
+    /// File status flags before switching
+    flags: libc::c_int,
+    /// Rest of a line the reader took only part of, written before anything new
+    pending: Vec<u8>,
+}
+
+impl Stdout {
+    pub fn non_blocking() -> io::Result<Self> {
+        let flags = unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_GETFL) };
+        if flags < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Stdout {
+            flags,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Write `line` and a newline; `Ok(false)` when the pipe is full and the line was
+    /// dropped. A line cut short stays queued so the stream never holds half a payload.
+    pub fn write_line(&mut self, line: &[u8]) -> io::Result<bool> {
+        self.flush()?;
+        if !self.pending.is_empty() {
+            return Ok(false);
+        }
+        self.pending.extend_from_slice(line);
+        self.pending.push(b'\n');
+        self.flush()?;
+        if self.pending.len() == line.len() + 1 {
+            // Nothing taken: a newer payload replaces it rather than queueing up
+            self.pending.clear();
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Whether part of a line is still waiting for the reader.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Queue `line` and a newline after anything pending, then wait up to `timeout`
+    /// for the reader to take all of it; for the last payload before exiting.
+    pub fn write_final(&mut self, line: &[u8], timeout: Duration) -> io::Result<()> {
+        self.pending.extend_from_slice(line);
+        self.pending.push(b'\n');
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.flush()?;
+            let left = deadline.saturating_duration_since(Instant::now());
+            if self.pending.is_empty() || left.is_zero() {
+                return Ok(());
+            }
+            let mut fd = libc::pollfd {
+                fd: libc::STDOUT_FILENO,
+                events: libc::POLLOUT,
+                revents: 0,
+            };
+            let millis = left.as_millis().max(1) as libc::c_int;
+            if unsafe { libc::poll(&mut fd, 1, millis) } < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Write as much of the pending bytes as the pipe takes.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let result = loop {
+            let rest = &self.pending[written..];
+            if rest.is_empty() {
+                break Ok(());
+            }
+            let len = unsafe { libc::write(libc::STDOUT_FILENO, rest.as_ptr() as *const libc::c_void, rest.len()) };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    io::ErrorKind::Interrupted => continue,
+                    io::ErrorKind::WouldBlock => break Ok(()),
+                    _ => break Err(err),
+                }
+            }
+            written += len as usize;
+        };
+        self.pending.drain(..written);
+        result
+    }
+}
+
+impl AsRawFd for Stdout {
+    fn as_raw_fd(&self) -> RawFd {
+        libc::STDOUT_FILENO
+    }
+}
+
+impl Drop for Stdout {
+    fn drop(&mut self) {
+        unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_SETFL, self.flags) };
+    }
+}
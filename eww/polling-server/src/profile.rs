@@ -0,0 +1,340 @@
+//! Power profile: ACPI platform profile, cpufreq governor and energy_performance_preference.
+//!
+//! Reading is done every poll from files opened once; switching writes all
+//! three so the bar can offer a profile toggle without power-profiles-daemon.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use crate::collector::Collector;
+use crate::cpu::{cpu_indices, PROC_STAT_PATH};
+use crate::source::Roots;
+use crate::{pread_file, push_json_str};
+
+/// ACPI platform profile (firmware fan/power tuning)
+const PLATFORM_PROFILE_PATH: &str = "/sys/firmware/acpi/platform_profile";
+/// Profiles the firmware accepts, space separated
+const PLATFORM_CHOICES_PATH: &str = "/sys/firmware/acpi/platform_profile_choices";
+/// Directory holding cpuN subdirectories
+const CPU_SYSFS_PATH: &str = "/sys/devices/system/cpu";
+/// Reported when cores disagree
+const MIXED: &str = "mixed";
+/// Order `profile next` cycles through when the firmware offers no choices
+const DEFAULT_CHOICES: &[&str] = &["low-power", "balanced", "performance"];
+/// The same without EPP, where low-power and balanced map to one governor
+const GOVERNOR_CHOICES: &[&str] = &["balanced", "performance"];
+
+/// Open profile files
+#[derive(Default)]
+pub struct ProfileFiles {
+    platform: Option<File>,
+    /// cpufreq/scaling_governor per core
+    governors: Vec<File>,
+    /// cpufreq/energy_performance_preference per core
+    epps: Vec<File>,
+}
+
+/// Power profile entry for output; fields are `None` where the kernel lacks the knob
+#[derive(Default)]
+pub struct ProfileEntry {
+    pub platform: Option<String>,
+    pub governor: Option<String>,
+    pub epp: Option<String>,
+}
+
+fn cpufreq_path(cpu: usize, file: &str) -> String {
+    format!("{}/cpu{}/cpufreq/{}", CPU_SYSFS_PATH, cpu, file)
+}
+
+/// Open the profile files under `roots` for the given cores (CPU numbers from /proc/stat).
+pub fn discover(roots: &Roots, cpus: &[usize]) -> ProfileFiles {
+    let open = |path: &str| File::open(roots.resolve(path)).ok();
+    ProfileFiles {
+        platform: open(PLATFORM_PROFILE_PATH),
+        governors: cpus
+            .iter()
+            .filter_map(|&cpu| open(&cpufreq_path(cpu, "scaling_governor")))
+            .collect(),
+        epps: cpus
+            .iter()
+            .filter_map(|&cpu| open(&cpufreq_path(cpu, "energy_performance_preference")))
+            .collect(),
+    }
+}
+
+/// Read a one-word sysfs file into `buf`.
+#[inline]
+fn read_word<'a>(file: &File, buf: &'a mut [u8]) -> Option<&'a str> {
+    let len = pread_file(file.as_raw_fd(), buf).ok()?;
+    let word = std::str::from_utf8(&buf[..len]).ok()?.trim();
+    (!word.is_empty()).then_some(word)
+}
+
+/// Set `slot` to `word`, allocating only when the value changed.
+#[inline]
+fn store(slot: &mut Option<String>, word: Option<&str>) {
+    match (slot.as_mut(), word) {
+        (Some(current), Some(word)) if current != word => {
+            current.clear();
+            current.push_str(word);
+        }
+        (Some(_), Some(_)) => {}
+        (None, Some(word)) => *slot = Some(word.to_string()),
+        (_, None) => *slot = None,
+    }
+}
+
+/// Store the common value of per-core files, or "mixed" when cores differ.
+fn read_uniform(files: &[File], buf: &mut [u8], slot: &mut Option<String>) {
+    let mut first = true;
+    for file in files {
+        let Some(word) = read_word(file, buf) else {
+            continue;
+        };
+        if first {
+            store(slot, Some(word));
+            first = false;
+        } else if slot.as_deref() != Some(word) {
+            store(slot, Some(MIXED));
+            return;
+        }
+    }
+    if first {
+        *slot = None;
+    }
+}
+
+/// Read the current profile into `entry`; all fields are `None` when no knob is available.
+#[inline]
+pub fn collect_profile(files: &ProfileFiles, buf: &mut [u8], entry: &mut ProfileEntry) {
+    let platform = files.platform.as_ref().and_then(|file| read_word(file, buf));
+    store(&mut entry.platform, platform);
+    read_uniform(&files.governors, buf, &mut entry.governor);
+    read_uniform(&files.epps, buf, &mut entry.epp);
+}
+
+/// Profiles accepted by `set_profile` under the cpufreq `driver`, in toggle order.
+fn choices(roots: &Roots, driver: &str) -> Vec<String> {
+    if let Ok(choices) = fs::read_to_string(roots.resolve(PLATFORM_CHOICES_PATH)) {
+        return choices.split_whitespace().map(str::to_string).collect();
+    }
+    let defaults = if has_epp(driver) { DEFAULT_CHOICES } else { GOVERNOR_CHOICES };
+    defaults.iter().map(|c| c.to_string()).collect()
+}
+
+/// Whether the cpufreq driver takes an EPP hint: intel_pstate and amd-pstate
+/// in active mode. In passive mode they register as intel_cpufreq and amd-pstate.
+fn has_epp(driver: &str) -> bool {
+    matches!(driver, "intel_pstate" | "amd-pstate-epp")
+}
+
+/// Governor and EPP matching a platform profile name under the cpufreq `driver`.
+/// EPP needs the `powersave` governor under the active-mode drivers, so only
+/// `performance` uses the performance governor there. Other drivers have no EPP
+/// and their `powersave` pins the lowest frequency, so they get `schedutil`.
+fn cpu_policy(driver: &str, profile: &str) -> (&'static str, Option<&'static str>) {
+    if !has_epp(driver) {
+        return match profile {
+            "performance" => ("performance", None),
+            _ => ("schedutil", None),
+        };
+    }
+    match profile {
+        "performance" => ("performance", Some("performance")),
+        "balanced" | "balanced-performance" => ("powersave", Some("balance_performance")),
+        "low-power" | "quiet" | "cool" => ("powersave", Some("power")),
+        _ => ("powersave", Some("balance_power")),
+    }
+}
+
+/// Active profile name: the platform profile, or on machines without one, the
+/// choice whose EPP (or governor, without EPP) the first core uses.
+fn current_profile(roots: &Roots, driver: &str, choices: &[String], cpus: &[usize]) -> Option<String> {
+    if let Ok(platform) = fs::read_to_string(roots.resolve(PLATFORM_PROFILE_PATH)) {
+        return Some(platform.trim().to_string());
+    }
+    let cpu = *cpus.first()?;
+    let read = |file: &str| fs::read_to_string(roots.resolve(&cpufreq_path(cpu, file))).ok();
+    let epp = read("energy_performance_preference");
+    let governor = read("scaling_governor");
+    choices
+        .iter()
+        .find(|profile| {
+            let (profile_governor, profile_epp) = cpu_policy(driver, profile);
+            match (&epp, &governor) {
+                (Some(epp), _) => profile_epp == Some(epp.trim()),
+                (None, Some(governor)) => profile_governor == governor.trim(),
+                (None, None) => false,
+            }
+        })
+        .cloned()
+}
+
+/// Write `value` to a per-core cpufreq file if the core offers it.
+fn write_cpufreq(roots: &Roots, cpu: usize, file: &str, available: &str, value: &str) -> io::Result<()> {
+    let offered = fs::read_to_string(roots.resolve(&cpufreq_path(cpu, available))).unwrap_or_default();
+    if !offered.split_whitespace().any(|choice| choice == value) {
+        return Ok(());
+    }
+    fs::write(roots.resolve(&cpufreq_path(cpu, file)), value)
+}
+
+/// Switch to `target` (a platform profile name, or `next` to cycle) on the given cores.
+/// Needs write access to sysfs, i.e. root or a udev rule.
+pub fn set_profile(roots: &Roots, target: &str, cpus: &[usize]) -> io::Result<()> {
+    // All cores share one driver; a machine without cpufreq gets only the platform profile
+    let driver = cpus
+        .first()
+        .and_then(|&cpu| fs::read_to_string(roots.resolve(&cpufreq_path(cpu, "scaling_driver"))).ok())
+        .unwrap_or_default();
+    let driver = driver.trim();
+
+    let choices = choices(roots, driver);
+    let profile = if target == "next" {
+        let current = current_profile(roots, driver, &choices, cpus).unwrap_or_default();
+        let position = choices.iter().position(|c| *c == current);
+        let next = position.map_or(0, |pos| (pos + 1) % choices.len());
+        choices
+            .get(next)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no profiles available"))?
+    } else if choices.iter().any(|c| c == target) {
+        target.to_string()
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown profile '{}', expected one of: {}", target, choices.join(", ")),
+        ));
+    };
+
+    let platform = roots.resolve(PLATFORM_PROFILE_PATH);
+    if fs::metadata(&platform).is_ok() {
+        fs::write(&platform, &profile)?;
+    }
+
+    // Governor first: EPP writes are rejected while the performance governor is active
+    let (governor, epp) = cpu_policy(driver, &profile);
+    for &cpu in cpus {
+        write_cpufreq(roots, cpu, "scaling_governor", "scaling_available_governors", governor)?;
+        if let Some(epp) = epp {
+            write_cpufreq(
+                roots,
+                cpu,
+                "energy_performance_preference",
+                "energy_performance_available_preferences",
+                epp,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Platform profile, governor and EPP ("r")
+#[derive(Default)]
+pub struct ProfileCollector {
+    files: ProfileFiles,
+    entry: ProfileEntry,
+    /// Profile names are a single word
+    buf: [u8; 32],
+}
+
+impl Collector for ProfileCollector {
+    fn name(&self) -> &'static str {
+        "profile"
+    }
+
+    fn key(&self) -> &'static str {
+        "r"
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        let path = roots.resolve(PROC_STAT_PATH);
+        let stat = fs::read(&path).map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        self.files = discover(roots, &cpu_indices(&stat));
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, _elapsed: f64) {
+        collect_profile(&self.files, &mut self.buf, &mut self.entry);
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        let profile = &self.entry;
+        if profile.platform.is_none() && profile.governor.is_none() && profile.epp.is_none() {
+            out.push_str("null");
+            return;
+        }
+        out.push('[');
+        for (idx, value) in [&profile.platform, &profile.governor, &profile.epp].into_iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            match value {
+                Some(value) => push_json_str(out, value),
+                None => out.push_str("null"),
+            }
+        }
+        out.push(']');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::Path;
+
+    use super::*;
+
+    fn write(root: &Path, path: &str, value: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, value).unwrap();
+    }
+
+    /// acpi-cpufreq: no platform_profile and no EPP, only governors
+    #[test]
+    fn cycles_governors_without_epp() {
+        let root = env::temp_dir().join(format!("polling-server-profile-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let sys = root.join("sys");
+        write(&root, "proc/stat", "cpu  2 0 0 2\ncpu0 1 0 0 1\ncpu1 1 0 0 1\n");
+        for cpu in ["cpu0", "cpu1"] {
+            let dir = format!("devices/system/cpu/{}/cpufreq", cpu);
+            write(&sys, &format!("{}/scaling_driver", dir), "acpi-cpufreq\n");
+            write(&sys, &format!("{}/scaling_governor", dir), "schedutil\n");
+            write(&sys, &format!("{}/scaling_available_governors", dir), "performance schedutil powersave\n");
+        }
+        let roots = Roots {
+            proc: root.join("proc"),
+            sys: sys.clone(),
+        };
+        let governor = || fs::read_to_string(sys.join("devices/system/cpu/cpu1/cpufreq/scaling_governor")).unwrap();
+
+        let choices = choices(&roots, "acpi-cpufreq");
+        assert_eq!(choices, ["balanced", "performance"]);
+        assert_eq!(current_profile(&roots, "acpi-cpufreq", &choices, &[0, 1]).as_deref(), Some("balanced"));
+        set_profile(&roots, "next", &[0, 1]).unwrap();
+        assert_eq!(governor(), "performance");
+        set_profile(&roots, "next", &[0, 1]).unwrap();
+        assert_eq!(governor(), "schedutil");
+        assert!(set_profile(&roots, "low-power", &[0, 1]).is_err());
+
+        // The collector reports the governor, which the bar falls back to
+        let mut collector = ProfileCollector::default();
+        collector.init(&roots).unwrap();
+        collector.sample(1.0);
+        let mut out = String::new();
+        collector.serialize(&mut out);
+        assert_eq!(out, r#"[null,"schedutil",null]"#);
+        write(&sys, "devices/system/cpu/cpu1/cpufreq/scaling_governor", "performance\n");
+        collector.sample(1.0);
+        out.clear();
+        collector.serialize(&mut out);
+        assert_eq!(out, r#"[null,"mixed",null]"#);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
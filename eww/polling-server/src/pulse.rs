@@ -0,0 +1,912 @@
+//! Native PulseAudio protocol client (works against pipewire-pulse).
+//!
+//! Speaks just enough of the protocol to authenticate, subscribe to sink,
+//! source and server changes, and query the default sink and source. Replaces
+//! `pactl subscribe` + `pamixer` forks with one long-lived socket.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::event_loop::{self, EventSource};
+use crate::Event;
+
+/// Protocol version we speak. 32 includes port availability; newer fields are never negotiated.
+const PROTOCOL_VERSION: u32 = 32;
+/// Oldest server version that reports device ports
+const MIN_PROTOCOL_VERSION: u32 = 16;
+/// Low bits of the AUTH reply carry the version, high bits shm/memfd flags
+const VERSION_MASK: u32 = 0x0000_FFFF;
+/// Packet descriptor: length, channel, offset hi, offset lo, flags (all u32 BE)
+const DESCRIPTOR_LEN: usize = 20;
+/// Channel number of control packets (everything else is stream audio)
+const CONTROL_CHANNEL: u32 = 0xFFFF_FFFF;
+/// Command + tag header of every control packet ('L' + u32 twice)
+const HEADER_LEN: usize = 10;
+/// Upper bound for a control packet, guards against garbage lengths
+const MAX_PACKET_LEN: usize = 1 << 20;
+/// Cookie length expected by AUTH (pipewire-pulse ignores the contents)
+const COOKIE_LEN: usize = 256;
+/// Sentinel for "no index" in requests and replies
+const INVALID_INDEX: u32 = 0xFFFF_FFFF;
+/// 100% volume
+const VOLUME_NORM: u64 = 0x10000;
+
+/// Commands
+const COMMAND_ERROR: u32 = 0;
+const COMMAND_REPLY: u32 = 2;
+const COMMAND_AUTH: u32 = 8;
+const COMMAND_SET_CLIENT_NAME: u32 = 9;
+const COMMAND_GET_SERVER_INFO: u32 = 20;
+const COMMAND_GET_SINK_INFO: u32 = 21;
+const COMMAND_GET_SOURCE_INFO: u32 = 23;
+const COMMAND_SUBSCRIBE: u32 = 35;
+const COMMAND_SUBSCRIBE_EVENT: u32 = 66;
+
+/// Error code for a missing sink/source
+const ERR_NOENTITY: u32 = 5;
+
+/// Subscription mask: sinks, sources, server (default device changes)
+const SUBSCRIPTION_MASK: u32 = 0x0001 | 0x0002 | 0x0080;
+/// Event facility bits and values
+const FACILITY_MASK: u32 = 0x000F;
+const FACILITY_SINK: u32 = 0;
+const FACILITY_SOURCE: u32 = 1;
+const FACILITY_SERVER: u32 = 7;
+
+/// Reconnect backoff bounds (pipewire restarts take a moment)
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+/// Longest wait for a reply or the rest of a packet; the event loop blocks meanwhile
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Audio state for output
+#[derive(Clone, PartialEq, Debug)]
+pub struct AudioEntry {
+    /// Default sink volume, average over channels (0-100, can exceed 100)
+    pub volume: u32,
+    /// Default sink mute
+    pub muted: bool,
+    /// Default sink description (e.g., "Built-in Audio Analog Stereo")
+    pub sink: String,
+    /// Active sink port description (e.g., "Headphones", "Speakers"), empty without ports
+    pub port: String,
+    /// Default source mute
+    pub mic_muted: bool,
+}
+
+/// Socket path: `$PULSE_SERVER` (unix: form only) or `$XDG_RUNTIME_DIR/pulse/native`.
+pub fn socket_path() -> Option<PathBuf> {
+    if let Ok(server) = env::var("PULSE_SERVER") {
+        if let Some(path) = server.strip_prefix("unix:") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    let runtime = env::var("XDG_RUNTIME_DIR").ok().filter(|s| !s.is_empty())?;
+    Some(Path::new(&runtime).join("pulse/native"))
+}
+
+/// Auth cookie from the usual locations; zeroes when there is none (pipewire doesn't check it).
+pub fn read_cookie() -> Vec<u8> {
+    let mut candidates = Vec::with_capacity(3);
+    if let Ok(path) = env::var("PULSE_COOKIE") {
+        candidates.push(PathBuf::from(path));
+    }
+    if let Ok(config) = env::var("XDG_CONFIG_HOME") {
+        candidates.push(Path::new(&config).join("pulse/cookie"));
+    }
+    if let Ok(home) = env::var("HOME") {
+        candidates.push(Path::new(&home).join(".config/pulse/cookie"));
+    }
+    candidates
+        .iter()
+        .filter_map(|path| fs::read(path).ok())
+        .find(|cookie| cookie.len() == COOKIE_LEN)
+        .unwrap_or_else(|| vec![0u8; COOKIE_LEN])
+}
+
+/// `Event::Audio` source. It reconnects with backoff whenever the server goes away,
+/// reporting `Event::Audio(None)` while disconnected.
+pub struct PulseSource {
+    path: PathBuf,
+    cookie: Vec<u8>,
+    conn: Option<Connection>,
+    defaults: Defaults,
+    last: Option<AudioEntry>,
+    /// Fires when the next reconnect attempt is due
+    retry: OwnedFd,
+    backoff: Duration,
+    /// An error was logged for the current outage
+    logged: bool,
+}
+
+impl PulseSource {
+    pub fn new(path: PathBuf, cookie: Vec<u8>) -> io::Result<Self> {
+        Ok(PulseSource {
+            path,
+            cookie,
+            conn: None,
+            defaults: Defaults { sink: None, source: None },
+            last: None,
+            retry: event_loop::timerfd()?,
+            backoff: RECONNECT_MIN,
+            logged: false,
+        })
+    }
+
+    fn connect(&mut self, updates: &mut Vec<Event>) {
+        let connected = Connection::connect(&self.path, &self.cookie)
+            .and_then(|mut conn| conn.query().map(|defaults| (conn, defaults)));
+        match connected {
+            Ok((conn, defaults)) => {
+                self.conn = Some(conn);
+                self.last = defaults.entry();
+                self.defaults = defaults;
+                updates.push(Event::Audio(self.last.clone()));
+            }
+            Err(err) => self.disconnect(err, updates),
+        }
+    }
+
+    /// Drop the connection and schedule the next attempt.
+    fn disconnect(&mut self, err: io::Error, updates: &mut Vec<Event>) {
+        // One line per outage, not per retry
+        if !self.logged {
+            eprintln!("polling-server: pulse: {}", err);
+            self.logged = true;
+        }
+        if self.conn.take().is_some() {
+            self.backoff = RECONNECT_MIN;
+            self.logged = false;
+            self.last = None;
+            updates.push(Event::Audio(None));
+        }
+        if let Err(err) = event_loop::arm(&self.retry, Instant::now() + self.backoff) {
+            eprintln!("polling-server: pulse: {}", err);
+        }
+        self.backoff = (self.backoff * 2).min(RECONNECT_MAX);
+    }
+
+    /// Handle the subscription events available without blocking.
+    fn read_events(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        let Some(conn) = self.conn.as_mut() else {
+            return Ok(());
+        };
+        loop {
+            // The socket is readable, so the first read doesn't block; after that
+            // only events queued while waiting for query replies are handled
+            let (event_type, index) = conn.next_event()?;
+            let relevant = match event_type & FACILITY_MASK {
+                FACILITY_SERVER => true,
+                FACILITY_SINK => Some(index) == self.defaults.sink.as_ref().map(|d| d.index),
+                FACILITY_SOURCE => Some(index) == self.defaults.source.as_ref().map(|d| d.index),
+                _ => false,
+            };
+            if relevant {
+                self.defaults = conn.query()?;
+                let entry = self.defaults.entry();
+                // Property churn (latency, state) doesn't change what we show
+                if entry != self.last {
+                    self.last = entry;
+                    updates.push(Event::Audio(self.last.clone()));
+                }
+            }
+            if conn.pending.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl EventSource for PulseSource {
+    fn name(&self) -> &'static str {
+        "pulse"
+    }
+
+    /// The control socket, or the retry timer while disconnected
+    fn fd(&self) -> RawFd {
+        match &self.conn {
+            Some(conn) => conn.stream.as_raw_fd(),
+            None => self.retry.as_raw_fd(),
+        }
+    }
+
+    fn start(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        self.connect(updates);
+        Ok(())
+    }
+
+    fn ready(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        if self.conn.is_none() {
+            event_loop::disarm(&self.retry)?;
+            self.connect(updates);
+            return Ok(());
+        }
+        if let Err(err) = self.read_events(updates) {
+            self.disconnect(err, updates);
+        }
+        Ok(())
+    }
+}
+
+/// Subset of sink/source info we care about
+struct DeviceInfo {
+    index: u32,
+    description: String,
+    /// Average channel volume in percent
+    volume: u32,
+    muted: bool,
+    /// Active port description
+    port: String,
+}
+
+/// Default sink and source
+struct Defaults {
+    sink: Option<DeviceInfo>,
+    source: Option<DeviceInfo>,
+}
+
+impl Defaults {
+    fn entry(&self) -> Option<AudioEntry> {
+        let sink = self.sink.as_ref()?;
+        Some(AudioEntry {
+            volume: sink.volume,
+            muted: sink.muted,
+            sink: sink.description.clone(),
+            port: sink.port.clone(),
+            mic_muted: self.source.as_ref().is_some_and(|source| source.muted),
+        })
+    }
+}
+
+/// Authenticated, subscribed control connection
+struct Connection {
+    stream: UnixStream,
+    /// Next request tag
+    tag: u32,
+    /// Negotiated protocol version
+    version: u32,
+    /// Subscription events (type, index) received while waiting for replies
+    pending: Vec<(u32, u32)>,
+}
+
+impl Connection {
+    fn connect(path: &Path, cookie: &[u8]) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut conn = Connection {
+            stream,
+            tag: 0,
+            version: PROTOCOL_VERSION,
+            pending: Vec::new(),
+        };
+
+        let reply = conn.request(COMMAND_AUTH, |w| {
+            w.u32(PROTOCOL_VERSION);
+            w.arbitrary(cookie);
+        })?;
+        let server_version = reply_reader(&reply).u32()? & VERSION_MASK;
+        conn.version = PROTOCOL_VERSION.min(server_version);
+        if conn.version < MIN_PROTOCOL_VERSION {
+            return Err(io::Error::other(format!("protocol version {} too old", server_version)));
+        }
+
+        conn.request(COMMAND_SET_CLIENT_NAME, |w| {
+            w.proplist(&[("application.name", "polling-server")]);
+        })?;
+        conn.request(COMMAND_SUBSCRIBE, |w| w.u32(SUBSCRIPTION_MASK))?;
+        Ok(conn)
+    }
+
+    /// Send a command and wait for its reply, queueing subscription events seen meanwhile.
+    fn request(&mut self, command: u32, args: impl FnOnce(&mut TagWriter)) -> io::Result<Vec<u8>> {
+        let tag = self.tag;
+        self.tag = self.tag.wrapping_add(1);
+
+        let mut writer = TagWriter::default();
+        writer.u32(command);
+        writer.u32(tag);
+        args(&mut writer);
+        write_packet(&mut self.stream, &writer.buf)?;
+
+        loop {
+            let packet = read_packet(&mut self.stream)?;
+            let mut reader = TagReader::new(&packet);
+            let reply_command = reader.u32()?;
+            let reply_tag = reader.u32()?;
+            if reply_command == COMMAND_SUBSCRIBE_EVENT {
+                self.pending.push((reader.u32()?, reader.u32()?));
+                continue;
+            }
+            if reply_tag != tag {
+                continue;
+            }
+            return match reply_command {
+                COMMAND_REPLY => Ok(packet),
+                COMMAND_ERROR => {
+                    let code = reader.u32()?;
+                    let kind = if code == ERR_NOENTITY {
+                        io::ErrorKind::NotFound
+                    } else {
+                        io::ErrorKind::Other
+                    };
+                    Err(io::Error::new(kind, format!("command {} failed with error {}", command, code)))
+                }
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected reply command")),
+            };
+        }
+    }
+
+    /// Next subscription event as `(type, index)`.
+    fn next_event(&mut self) -> io::Result<(u32, u32)> {
+        if !self.pending.is_empty() {
+            return Ok(self.pending.remove(0));
+        }
+        loop {
+            let packet = read_packet(&mut self.stream)?;
+            let mut reader = TagReader::new(&packet);
+            if reader.u32()? == COMMAND_SUBSCRIBE_EVENT {
+                reader.u32()?;
+                return Ok((reader.u32()?, reader.u32()?));
+            }
+        }
+    }
+
+    /// Default sink and source, looked up by the names in the server info.
+    fn query(&mut self) -> io::Result<Defaults> {
+        let reply = self.request(COMMAND_GET_SERVER_INFO, |_| {})?;
+        let mut reader = reply_reader(&reply);
+        // package name, version, user name, host name, sample spec
+        for _ in 0..5 {
+            reader.skip()?;
+        }
+        let sink_name = reader.string()?;
+        let source_name = reader.string()?;
+
+        Ok(Defaults {
+            sink: self.device_info(COMMAND_GET_SINK_INFO, sink_name.as_deref())?,
+            source: self.device_info(COMMAND_GET_SOURCE_INFO, source_name.as_deref())?,
+        })
+    }
+
+    /// GET_SINK_INFO / GET_SOURCE_INFO by name; `None` when unset or gone.
+    fn device_info(&mut self, command: u32, name: Option<&str>) -> io::Result<Option<DeviceInfo>> {
+        let Some(name) = name else {
+            return Ok(None);
+        };
+        let reply = match self.request(command, |w| {
+            w.u32(INVALID_INDEX);
+            w.string(name);
+        }) {
+            Ok(reply) => reply,
+            // Removed between the server info and this request
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        parse_device_info(&reply, self.version).map(Some)
+    }
+}
+
+/// Parse a sink or source info reply; both share the layout up to the active port.
+fn parse_device_info(reply: &[u8], version: u32) -> io::Result<DeviceInfo> {
+    let mut r = reply_reader(reply);
+    let index = r.u32()?;
+    let name = r.string()?.unwrap_or_default();
+    let description = r.string()?.unwrap_or_else(|| name.clone());
+    // sample spec, channel map, owner module
+    for _ in 0..3 {
+        r.skip()?;
+    }
+    let volumes = r.cvolume()?;
+    let muted = r.bool()?;
+    // monitor index + name, latency, driver, flags
+    for _ in 0..5 {
+        r.skip()?;
+    }
+    if version >= 13 {
+        // proplist, configured latency
+        r.skip()?;
+        r.skip()?;
+    }
+    if version >= 15 {
+        // base volume, state, volume steps, card
+        for _ in 0..4 {
+            r.skip()?;
+        }
+    }
+
+    let mut port = String::new();
+    if version >= 16 {
+        let n_ports = r.u32()?;
+        let mut ports = Vec::with_capacity(n_ports.min(32) as usize);
+        for _ in 0..n_ports {
+            let port_name = r.string()?.unwrap_or_default();
+            let port_description = r.string()?.unwrap_or_default();
+            // priority
+            r.skip()?;
+            if version >= 24 {
+                // availability
+                r.skip()?;
+            }
+            ports.push((port_name, port_description));
+        }
+        if let Some(active) = r.string()? {
+            port = ports
+                .into_iter()
+                .find(|(port_name, _)| *port_name == active)
+                .map_or(active, |(_, port_description)| port_description);
+        }
+    }
+
+    let volume = if volumes.is_empty() {
+        0
+    } else {
+        let average = volumes.iter().map(|&v| v as u64).sum::<u64>() / volumes.len() as u64;
+        ((average * 100 + VOLUME_NORM / 2) / VOLUME_NORM) as u32
+    };
+
+    Ok(DeviceInfo {
+        index,
+        description,
+        volume,
+        muted,
+        port,
+    })
+}
+
+fn write_packet(stream: &mut UnixStream, payload: &[u8]) -> io::Result<()> {
+    let mut packet = Vec::with_capacity(DESCRIPTOR_LEN + payload.len());
+    packet.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    packet.extend_from_slice(&CONTROL_CHANNEL.to_be_bytes());
+    packet.extend_from_slice(&[0u8; 12]);
+    packet.extend_from_slice(payload);
+    stream.write_all(&packet)
+}
+
+/// Read the next control packet payload, skipping any stream data.
+fn read_packet(stream: &mut UnixStream) -> io::Result<Vec<u8>> {
+    loop {
+        let mut descriptor = [0u8; DESCRIPTOR_LEN];
+        stream.read_exact(&mut descriptor)?;
+        let len = u32::from_be_bytes([descriptor[0], descriptor[1], descriptor[2], descriptor[3]]) as usize;
+        let channel = u32::from_be_bytes([descriptor[4], descriptor[5], descriptor[6], descriptor[7]]);
+        if len > MAX_PACKET_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "packet too large"));
+        }
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload)?;
+        if channel == CONTROL_CHANNEL {
+            return Ok(payload);
+        }
+    }
+}
+
+/// Reader positioned after the command + tag header of a reply.
+fn reply_reader(reply: &[u8]) -> TagReader<'_> {
+    TagReader {
+        data: reply,
+        pos: HEADER_LEN.min(reply.len()),
+    }
+}
+
+fn malformed() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed tagstruct")
+}
+
+/// Tagstruct type tags
+const TAG_STRING: u8 = b't';
+const TAG_STRING_NULL: u8 = b'N';
+const TAG_U32: u8 = b'L';
+const TAG_U8: u8 = b'B';
+const TAG_U64: u8 = b'R';
+const TAG_S64: u8 = b'r';
+const TAG_SAMPLE_SPEC: u8 = b'a';
+const TAG_ARBITRARY: u8 = b'x';
+const TAG_BOOLEAN_TRUE: u8 = b'1';
+const TAG_BOOLEAN_FALSE: u8 = b'0';
+const TAG_TIMEVAL: u8 = b'T';
+const TAG_USEC: u8 = b'U';
+const TAG_CHANNEL_MAP: u8 = b'm';
+const TAG_CVOLUME: u8 = b'v';
+const TAG_PROPLIST: u8 = b'P';
+const TAG_VOLUME: u8 = b'V';
+const TAG_FORMAT_INFO: u8 = b'f';
+
+/// Cursor over a tagstruct. Every value is prefixed by its type tag,
+/// so fields we don't need can be skipped without knowing their meaning.
+struct TagReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> TagReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        TagReader { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or_else(malformed)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn raw_u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn expect(&mut self, tag: u8) -> io::Result<()> {
+        if self.byte()? == tag {
+            Ok(())
+        } else {
+            Err(malformed())
+        }
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.expect(TAG_U32)?;
+        self.raw_u32()
+    }
+
+    fn bool(&mut self) -> io::Result<bool> {
+        match self.byte()? {
+            TAG_BOOLEAN_TRUE => Ok(true),
+            TAG_BOOLEAN_FALSE => Ok(false),
+            _ => Err(malformed()),
+        }
+    }
+
+    /// String or null string
+    fn string(&mut self) -> io::Result<Option<String>> {
+        match self.byte()? {
+            TAG_STRING_NULL => Ok(None),
+            TAG_STRING => {
+                let rest = &self.data[self.pos..];
+                let len = rest.iter().position(|&b| b == 0).ok_or_else(malformed)?;
+                let value = String::from_utf8_lossy(&rest[..len]).into_owned();
+                self.pos += len + 1;
+                Ok(Some(value))
+            }
+            _ => Err(malformed()),
+        }
+    }
+
+    /// Per-channel volumes
+    fn cvolume(&mut self) -> io::Result<Vec<u32>> {
+        self.expect(TAG_CVOLUME)?;
+        let channels = self.byte()?;
+        (0..channels).map(|_| self.raw_u32()).collect()
+    }
+
+    /// Skip one value of any type.
+    fn skip(&mut self) -> io::Result<()> {
+        match self.byte()? {
+            TAG_STRING => {
+                let len = self.data[self.pos..].iter().position(|&b| b == 0).ok_or_else(malformed)?;
+                self.pos += len + 1;
+            }
+            TAG_STRING_NULL | TAG_BOOLEAN_TRUE | TAG_BOOLEAN_FALSE => {}
+            TAG_U8 => {
+                self.take(1)?;
+            }
+            TAG_U32 | TAG_VOLUME => {
+                self.take(4)?;
+            }
+            TAG_U64 | TAG_S64 | TAG_USEC | TAG_TIMEVAL => {
+                self.take(8)?;
+            }
+            TAG_SAMPLE_SPEC => {
+                self.take(6)?;
+            }
+            TAG_ARBITRARY => {
+                let len = self.raw_u32()? as usize;
+                self.take(len)?;
+            }
+            TAG_CHANNEL_MAP => {
+                let channels = self.byte()? as usize;
+                self.take(channels)?;
+            }
+            TAG_CVOLUME => {
+                let channels = self.byte()? as usize;
+                self.take(channels * 4)?;
+            }
+            TAG_PROPLIST => {
+                // key, length, value triples terminated by a null string
+                while self.data.get(self.pos) != Some(&TAG_STRING_NULL) {
+                    self.skip()?;
+                    self.skip()?;
+                    self.skip()?;
+                }
+                self.pos += 1;
+            }
+            TAG_FORMAT_INFO => {
+                // encoding + proplist
+                self.skip()?;
+                self.skip()?;
+            }
+            _ => return Err(malformed()),
+        }
+        Ok(())
+    }
+}
+
+/// Tagstruct builder for requests
+#[derive(Default)]
+struct TagWriter {
+    buf: Vec<u8>,
+}
+
+impl TagWriter {
+    fn u32(&mut self, value: u32) {
+        self.buf.push(TAG_U32);
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.buf.push(TAG_STRING);
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn arbitrary(&mut self, value: &[u8]) {
+        self.buf.push(TAG_ARBITRARY);
+        self.buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        self.buf.extend_from_slice(value);
+    }
+
+    /// String properties; values are stored NUL-terminated like libpulse does.
+    fn proplist(&mut self, props: &[(&str, &str)]) {
+        self.buf.push(TAG_PROPLIST);
+        for (key, value) in props {
+            let mut data = value.as_bytes().to_vec();
+            data.push(0);
+            self.string(key);
+            self.u32(data.len() as u32);
+            self.arbitrary(&data);
+        }
+        self.buf.push(TAG_STRING_NULL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    use crate::event_loop::EventLoop;
+
+    /// Scripted stand-in for pipewire-pulse: one sink with two ports and one source.
+    struct FakeServer {
+        stream: UnixStream,
+        sink_muted: bool,
+        sink_volume: u32,
+        active_port: &'static str,
+        source_muted: bool,
+    }
+
+    impl FakeServer {
+        fn new(stream: UnixStream) -> Self {
+            FakeServer {
+                stream,
+                sink_muted: false,
+                sink_volume: 0x8000,
+                active_port: "analog-output-speaker",
+                source_muted: true,
+            }
+        }
+
+        /// Read one request, returning its command and tag.
+        fn read_request(&mut self) -> (u32, u32) {
+            let packet = read_packet(&mut self.stream).unwrap();
+            let mut reader = TagReader::new(&packet);
+            (reader.u32().unwrap(), reader.u32().unwrap())
+        }
+
+        fn reply(&mut self, tag: u32, body: impl FnOnce(&mut TagWriter)) {
+            let mut w = TagWriter::default();
+            w.u32(COMMAND_REPLY);
+            w.u32(tag);
+            body(&mut w);
+            write_packet(&mut self.stream, &w.buf).unwrap();
+        }
+
+        fn event(&mut self, event_type: u32, index: u32) {
+            let mut w = TagWriter::default();
+            w.u32(COMMAND_SUBSCRIBE_EVENT);
+            w.u32(INVALID_INDEX);
+            w.u32(event_type);
+            w.u32(index);
+            write_packet(&mut self.stream, &w.buf).unwrap();
+        }
+
+        /// Serve requests until the client has subscribed and queried defaults once.
+        fn handshake(&mut self) {
+            let (command, tag) = self.read_request();
+            assert_eq!(command, COMMAND_AUTH);
+            // Server speaks a newer version with the shm flag set
+            self.reply(tag, |w| w.u32(35 | 0x8000_0000));
+            let (command, tag) = self.read_request();
+            assert_eq!(command, COMMAND_SET_CLIENT_NAME);
+            self.reply(tag, |w| w.u32(7));
+            let (command, tag) = self.read_request();
+            assert_eq!(command, COMMAND_SUBSCRIBE);
+            self.reply(tag, |_| {});
+            self.serve_query();
+        }
+
+        /// Answer the server info + sink + source round trips.
+        fn serve_query(&mut self) {
+            let (command, tag) = self.read_request();
+            assert_eq!(command, COMMAND_GET_SERVER_INFO);
+            self.reply(tag, |w| {
+                w.string("pulseaudio");
+                w.string("15.0.0");
+                w.string("user");
+                w.string("host");
+                sample_spec(w);
+                w.string("alsa_output.pci");
+                w.string("alsa_input.pci");
+                w.u32(0);
+                channel_map(w);
+            });
+
+            let (command, tag) = self.read_request();
+            assert_eq!(command, COMMAND_GET_SINK_INFO);
+            let (muted, volume, port) = (self.sink_muted, self.sink_volume, self.active_port);
+            self.reply(tag, |w| device_info(w, 3, "Built-in Audio", volume, muted, Some(port)));
+
+            let (command, tag) = self.read_request();
+            assert_eq!(command, COMMAND_GET_SOURCE_INFO);
+            let muted = self.source_muted;
+            self.reply(tag, |w| device_info(w, 4, "Microphone", 0x10000, muted, None));
+        }
+    }
+
+    fn sample_spec(w: &mut TagWriter) {
+        w.buf.extend_from_slice(&[TAG_SAMPLE_SPEC, 3, 2]);
+        w.buf.extend_from_slice(&48000u32.to_be_bytes());
+    }
+
+    fn channel_map(w: &mut TagWriter) {
+        w.buf.extend_from_slice(&[TAG_CHANNEL_MAP, 2, 1, 2]);
+    }
+
+    fn usec(w: &mut TagWriter) {
+        w.buf.push(TAG_USEC);
+        w.buf.extend_from_slice(&0u64.to_be_bytes());
+    }
+
+    /// Sink/source info at protocol version 32 (ports carry availability).
+    fn device_info(w: &mut TagWriter, index: u32, description: &str, volume: u32, muted: bool, port: Option<&str>) {
+        w.u32(index);
+        w.string("device");
+        w.string(description);
+        sample_spec(w);
+        channel_map(w);
+        w.u32(1);
+        w.buf.extend_from_slice(&[TAG_CVOLUME, 2]);
+        w.buf.extend_from_slice(&volume.to_be_bytes());
+        w.buf.extend_from_slice(&volume.to_be_bytes());
+        w.buf.push(if muted { TAG_BOOLEAN_TRUE } else { TAG_BOOLEAN_FALSE });
+        w.u32(INVALID_INDEX);
+        w.buf.push(TAG_STRING_NULL);
+        usec(w);
+        w.string("PipeWire");
+        w.u32(0);
+        w.proplist(&[("device.class", "sound")]);
+        usec(w);
+        w.buf.push(TAG_VOLUME);
+        w.buf.extend_from_slice(&0x10000u32.to_be_bytes());
+        w.u32(0);
+        w.u32(65537);
+        w.u32(1);
+        match port {
+            Some(active) => {
+                w.u32(2);
+                for (name, description) in [("analog-output-speaker", "Speakers"), ("analog-output-headphones", "Headphones")] {
+                    w.string(name);
+                    w.string(description);
+                    w.u32(100);
+                    w.u32(2);
+                }
+                w.string(active);
+            }
+            None => {
+                w.u32(0);
+                w.buf.push(TAG_STRING_NULL);
+            }
+        }
+        // formats
+        w.buf.extend_from_slice(&[TAG_U8, 0]);
+    }
+
+    fn listener() -> (UnixListener, PathBuf) {
+        let path = env::temp_dir().join(format!("polling-server-pulse-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        (UnixListener::bind(&path).unwrap(), path)
+    }
+
+    fn next_audio(rx: &Receiver<Event>) -> Option<AudioEntry> {
+        match rx.recv_timeout(Duration::from_secs(5)).expect("no event from the event loop") {
+            Event::Audio(entry) => entry,
+            _ => panic!("unexpected event"),
+        }
+    }
+
+    #[test]
+    fn follows_default_sink_and_reconnects() {
+        let (listener, path) = listener();
+        let (tx, rx) = mpsc::channel();
+        let source = PulseSource::new(path.clone(), vec![0u8; COOKIE_LEN]).unwrap();
+        thread::spawn(move || {
+            let mut event_loop = EventLoop::new().unwrap();
+            let mut updates = Vec::new();
+            event_loop.add(Box::new(source), &mut updates).unwrap();
+            loop {
+                for update in updates.drain(..) {
+                    if tx.send(update).is_err() {
+                        return;
+                    }
+                }
+                let deadline = Instant::now() + Duration::from_secs(60);
+                event_loop.wait(deadline, &mut updates).unwrap();
+            }
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut server = FakeServer::new(stream);
+        server.handshake();
+        assert_eq!(
+            next_audio(&rx),
+            Some(AudioEntry {
+                volume: 50,
+                muted: false,
+                sink: "Built-in Audio".to_string(),
+                port: "Speakers".to_string(),
+                mic_muted: true,
+            })
+        );
+
+        // Unrelated sink changes are ignored, the default sink triggers a requery
+        server.event(FACILITY_SINK | 0x10, 9);
+        server.sink_muted = true;
+        server.active_port = "analog-output-headphones";
+        server.event(FACILITY_SINK | 0x10, 3);
+        server.serve_query();
+        let entry = next_audio(&rx).unwrap();
+        assert!(entry.muted);
+        assert_eq!(entry.port, "Headphones");
+
+        // pipewire restart: disconnect is reported, then state comes back
+        drop(server);
+        assert_eq!(next_audio(&rx), None);
+        let (stream, _) = listener.accept().unwrap();
+        let mut server = FakeServer::new(stream);
+        server.sink_volume = 0x10000;
+        server.handshake();
+        assert_eq!(next_audio(&rx).unwrap().volume, 100);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rejects_truncated_tagstruct() {
+        let mut w = TagWriter::default();
+        w.u32(COMMAND_REPLY);
+        w.u32(0);
+        w.u32(3);
+        w.buf.push(TAG_STRING);
+        w.buf.extend_from_slice(b"no terminator");
+        assert!(parse_device_info(&w.buf, PROTOCOL_VERSION).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! RAPL energy counters from the powercap framework.
+//!
+//! `intel-rapl:N` zones are packages, `intel-rapl:N:M` their subzones (core,
+//! uncore, dram). AMD Zen exposes its package/core counters under the same
+//! names through the powercap driver.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::rc::Rc;
+
+use crate::collector::Collector;
+use crate::source::Roots;
+use crate::{ftoa_f64, parse_number_from_line, pread_file, wrapping_counter_delta, MIN_ELAPSED};
+
+/// Directory holding the powercap zones
+const POWERCAP_PATH: &str = "/sys/class/powercap";
+/// Zone directory prefix (skips `intel-rapl-mmio`, which duplicates the package)
+const RAPL_PREFIX: &str = "intel-rapl:";
+
+/// One RAPL zone with an open `energy_uj` counter
+pub struct RaplZone {
+    /// Output label (e.g., "package-0", "core-0"), shared with the entries
+    label: Rc<str>,
+    /// Kept open and re-read with pread
+    energy_file: File,
+    /// Counter range; the counter wraps to 0 after this many microjoules
+    max_energy_uj: u64,
+    /// Previous counter value
+    prev_uj: Option<u64>,
+}
+
+/// Power draw entry for output
+pub struct PowerEntry {
+    /// Zone label
+    pub zone: Rc<str>,
+    /// Average draw over the last interval in watts, `None` without a previous
+    /// sample (the first one, after resume or a counter reset)
+    pub watts: Option<f64>,
+}
+
+/// Find and open all readable RAPL zones, sorted by label.
+/// `energy_uj` is root-only since Linux 5.10; unreadable zones are skipped, and
+/// when no zone is readable the error says why the section stays empty.
+pub fn discover(roots: &Roots) -> io::Result<Vec<RaplZone>> {
+    let Ok(dir) = fs::read_dir(roots.resolve(POWERCAP_PATH)) else {
+        return Ok(Vec::new());
+    };
+    let mut unreadable = None;
+    let mut zones: Vec<RaplZone> = dir
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name();
+            let id = file_name.to_str()?.strip_prefix(RAPL_PREFIX)?.to_string();
+            let path = entry.path();
+
+            let name = fs::read_to_string(path.join("name")).ok()?;
+            let max_energy_uj = parse_number_from_line(fs::read(path.join("max_energy_range_uj")).ok()?.as_slice());
+            let energy_path = path.join("energy_uj");
+            let energy_file = match File::open(&energy_path) {
+                Ok(file) => file,
+                Err(err) => {
+                    unreadable = Some(io::Error::new(err.kind(), format!("{}: {}", energy_path.display(), err)));
+                    return None;
+                }
+            };
+
+            // Subzone ids are "package:index"; tag them with their package so
+            // multi-socket machines don't report two bare "core" zones
+            let name = name.trim();
+            let label = match id.split_once(':') {
+                Some((package, _)) => format!("{}-{}", name, package),
+                None => name.to_string(),
+            };
+            Some(RaplZone {
+                label: label.into(),
+                energy_file,
+                max_energy_uj,
+                prev_uj: None,
+            })
+        })
+        .collect();
+    if let (true, Some(err)) = (zones.is_empty(), unreadable) {
+        return Err(err);
+    }
+    zones.sort_by(|a, b| a.label.cmp(&b.label));
+    Ok(zones)
+}
+
+/// Sample every zone and convert energy deltas to watts.
+/// Same delta-over-elapsed approach as the disk and network rates.
+#[inline]
+pub fn collect_power(elapsed: f64, zones: &mut [RaplZone], buf: &mut [u8], entries: &mut Vec<PowerEntry>) {
+    let elapsed = elapsed.max(MIN_ELAPSED);
+
+    for zone in zones.iter_mut() {
+        let Ok(len) = pread_file(zone.energy_file.as_raw_fd(), buf) else {
+            continue;
+        };
+        let energy_uj = parse_number_from_line(&buf[..len]);
+
+        // The counter wraps past max_energy_range_uj
+        let delta_uj = zone
+            .prev_uj
+            .and_then(|prev| wrapping_counter_delta(prev, energy_uj, zone.max_energy_uj));
+        zone.prev_uj = Some(energy_uj);
+
+        entries.push(PowerEntry {
+            zone: Rc::clone(&zone.label),
+            watts: delta_uj.map(|delta| delta as f64 / 1_000_000.0 / elapsed),
+        });
+    }
+}
+
+/// Power draw per RAPL zone ("p")
+#[derive(Default)]
+pub struct PowerCollector {
+    zones: Vec<RaplZone>,
+    entries: Vec<PowerEntry>,
+    /// energy_uj holds a single value
+    buf: [u8; 32],
+}
+
+impl Collector for PowerCollector {
+    fn name(&self) -> &'static str {
+        "power"
+    }
+
+    fn key(&self) -> &'static str {
+        "p"
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.zones = discover(roots)?;
+        self.entries = Vec::with_capacity(self.zones.len());
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, elapsed: f64) {
+        self.entries.clear();
+        collect_power(elapsed, &mut self.zones, &mut self.buf, &mut self.entries);
+    }
+
+    fn reset_baselines(&mut self) {
+        for zone in self.zones.iter_mut() {
+            zone.prev_uj = None;
+        }
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        out.push('[');
+        for (idx, entry) in self.entries.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str("[\"");
+            out.push_str(&entry.zone);
+            out.push_str("\",");
+            match entry.watts {
+                Some(watts) => ftoa_f64(out, watts, 2),
+                None => out.push_str("null"),
+            }
+            out.push(']');
+        }
+        out.push(']');
+    }
+}
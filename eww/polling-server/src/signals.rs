@@ -0,0 +1,80 @@
+//! SIGHUP, SIGTERM and SIGINT read from a signalfd in the event loop.
+//!
+//! The signals are blocked for the whole process, so they are only ever
+//! delivered through the signalfd and never interrupt a read or a write.
+
+use std::io;
+use std::mem::{self, MaybeUninit};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use crate::event_loop::EventSource;
+use crate::events::Event;
+
+/// Signals handled by the daemon
+const SIGNALS: [libc::c_int; 3] = [libc::SIGHUP, libc::SIGTERM, libc::SIGINT];
+
+/// signalfd turning SIGHUP into `Event::Reload` and SIGTERM/SIGINT into `Event::Shutdown`
+pub struct SignalSource {
+    fd: OwnedFd,
+}
+
+impl SignalSource {
+    /// Block the handled signals and open a signalfd for them.
+    /// The mask is per thread, so this blocks them on the thread running the event loop.
+    pub fn new() -> io::Result<Self> {
+        let set = signal_set();
+        let rc = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) };
+        if rc != 0 {
+            return Err(io::Error::from_raw_os_error(rc));
+        }
+        let fd = unsafe { libc::signalfd(-1, &set, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SignalSource {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+}
+
+impl EventSource for SignalSource {
+    fn name(&self) -> &'static str {
+        "signals"
+    }
+
+    fn fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    fn ready(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        let size = mem::size_of::<libc::signalfd_siginfo>();
+        loop {
+            let mut info = MaybeUninit::<libc::signalfd_siginfo>::uninit();
+            let len = unsafe { libc::read(self.fd.as_raw_fd(), info.as_mut_ptr() as *mut libc::c_void, size) };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    io::ErrorKind::WouldBlock => Ok(()),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => Err(err),
+                };
+            }
+            if len as usize != size {
+                return Ok(());
+            }
+            let signal = unsafe { info.assume_init() }.ssi_signo as libc::c_int;
+            updates.push(if signal == libc::SIGHUP { Event::Reload } else { Event::Shutdown });
+        }
+    }
+}
+
+fn signal_set() -> libc::sigset_t {
+    let mut set = MaybeUninit::<libc::sigset_t>::uninit();
+    unsafe {
+        libc::sigemptyset(set.as_mut_ptr());
+        for signal in SIGNALS {
+            libc::sigaddset(set.as_mut_ptr(), signal);
+        }
+        set.assume_init()
+    }
+}
@@ -0,0 +1,127 @@
+//! /proc files read every poll, isolated so one failing file only blanks its own section.
+//!
+//! A file that fails to open or read is closed and reopened with exponential
+//! backoff; the error is reported in the payload until a read succeeds again.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::pread_all;
+
+/// Reopen backoff bounds, also used to retry a failed collector `init`
+pub(crate) const REOPEN_MIN: Duration = Duration::from_millis(500);
+pub(crate) const REOPEN_MAX: Duration = Duration::from_secs(30);
+
+/// Where procfs and sysfs are mounted, e.g. the host's `/proc` bind-mounted at
+/// `/host/proc` in a container, or a fixture directory in tests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roots {
+    pub proc: PathBuf,
+    pub sys: PathBuf,
+}
+
+impl Default for Roots {
+    fn default() -> Self {
+        Roots {
+            proc: PathBuf::from("/proc"),
+            sys: PathBuf::from("/sys"),
+        }
+    }
+}
+
+impl Roots {
+    /// `path`, an absolute `/proc` or `/sys` path like the collectors' constants,
+    /// under these roots. Other paths are returned unchanged.
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        if let Some(rest) = path.strip_prefix("/proc/") {
+            self.proc.join(rest)
+        } else if let Some(rest) = path.strip_prefix("/sys/") {
+            self.sys.join(rest)
+        } else {
+            PathBuf::from(path)
+        }
+    }
+}
+
+/// A file kept open for pread, with its grow-once read buffer
+pub struct ProcSource {
+    /// Path under the default roots
+    default_path: &'static str,
+    path: PathBuf,
+    file: Option<File>,
+    buf: Vec<u8>,
+    /// Error of the last failed open or read, cleared by the next successful read
+    error: Option<String>,
+    /// Earliest time of the next reopen attempt
+    retry_at: Instant,
+    backoff: Duration,
+}
+
+impl ProcSource {
+    /// Source for `path` with an initial buffer of `capacity` bytes.
+    /// The file is opened by the first read; failing to open is not fatal.
+    pub fn new(path: &'static str, capacity: usize) -> Self {
+        ProcSource {
+            default_path: path,
+            path: PathBuf::from(path),
+            file: None,
+            buf: vec![0u8; capacity],
+            error: None,
+            retry_at: Instant::now(),
+            backoff: REOPEN_MIN,
+        }
+    }
+
+    /// Read the file under `roots` from now on.
+    pub fn relocate(&mut self, roots: &Roots) {
+        self.path = roots.resolve(self.default_path);
+        self.file = None;
+    }
+
+    /// Read the whole file, `None` while it is failing.
+    #[inline]
+    pub fn read(&mut self) -> Option<&[u8]> {
+        if self.file.is_none() {
+            if Instant::now() < self.retry_at {
+                return None;
+            }
+            match File::open(&self.path) {
+                Ok(file) => self.file = Some(file),
+                Err(err) => {
+                    self.fail(err);
+                    return None;
+                }
+            }
+        }
+
+        let fd = self.file.as_ref()?.as_raw_fd();
+        match pread_all(fd, &mut self.buf) {
+            Ok(len) => {
+                self.error = None;
+                self.backoff = REOPEN_MIN;
+                Some(&self.buf[..len])
+            }
+            Err(err) => {
+                self.fail(err);
+                None
+            }
+        }
+    }
+
+    /// Error to report for this source's section, if it is failing.
+    #[inline]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Record a failure, close the file and schedule a reopen.
+    fn fail(&mut self, err: io::Error) {
+        self.error = Some(format!("{}: {}", self.path.display(), err));
+        self.file = None;
+        self.retry_at = Instant::now() + self.backoff;
+        self.backoff = (self.backoff * 2).min(REOPEN_MAX);
+    }
+}
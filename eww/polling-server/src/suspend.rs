@@ -0,0 +1,273 @@
+//! Suspend statistics (`/sys/power/suspend_stats`) and wakeup sources (`/sys/class/wakeup`).
+//!
+//! Time asleep is measured as growth of CLOCK_BOOTTIME over CLOCK_MONOTONIC:
+//! both tick while awake, only BOOTTIME keeps counting while suspended.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use crate::collector::Collector;
+use crate::source::Roots;
+use crate::{counter_delta, ftoa_f64, itoa_u64, parse_number_from_line, pread_file, push_json_str};
+
+/// Kernel suspend counters
+const SUSPEND_STATS_PATH: &str = "/sys/power/suspend_stats";
+/// One wakeupN directory per wakeup source
+const WAKEUP_PATH: &str = "/sys/class/wakeup";
+/// Clock gap growth that counts as a suspend. The gap only moves while suspended,
+/// so this just has to clear the time between the two clock_gettime calls.
+pub const MIN_SLEEP_SECS: f64 = 0.1;
+
+/// Seconds on a kernel clock.
+#[inline]
+pub fn clock_secs(clock: libc::clockid_t) -> f64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // Cannot fail for BOOTTIME/MONOTONIC with a valid pointer
+    unsafe {
+        libc::clock_gettime(clock, &mut ts);
+    }
+    ts.tv_sec as f64 + ts.tv_nsec as f64 / 1e9
+}
+
+/// Total time spent suspended since boot.
+#[inline]
+pub fn suspended_secs() -> f64 {
+    clock_secs(libc::CLOCK_BOOTTIME) - clock_secs(libc::CLOCK_MONOTONIC)
+}
+
+/// One wakeup source with open counters
+struct WakeupSource {
+    name: String,
+    event_count: File,
+    wakeup_count: File,
+    active_time_ms: File,
+    prev_events: u64,
+    prev_wakeups: u64,
+    prev_active_ms: u64,
+}
+
+/// Suspend collector state
+#[derive(Default)]
+pub struct SuspendState {
+    success: Option<File>,
+    fail: Option<File>,
+    last_failed_step: Option<File>,
+    sources: Vec<WakeupSource>,
+    /// Wakeup source directory, rescanned after resume
+    wakeup_dir: PathBuf,
+    /// `suspended_secs()` at the previous sample
+    prev_suspended: f64,
+    /// Duration of the most recent suspend
+    last_sleep_secs: f64,
+    /// Wakeup source blamed for the most recent resume
+    woken_by: Option<String>,
+}
+
+/// Wakeup source activity during the last interval
+pub struct WakeupEntry {
+    pub name: String,
+    /// Events signaled
+    pub events: u64,
+    /// Time the source held the system awake, in ms
+    pub active_ms: u64,
+}
+
+/// Suspend entry for output
+pub struct SuspendEntry {
+    /// Successful suspends since boot
+    pub success: u64,
+    /// Failed suspend attempts since boot
+    pub fail: u64,
+    /// Step of the last failure (e.g., "suspend_noirq"), `None` if it never failed
+    pub last_failed_step: Option<String>,
+    /// How long the last suspend lasted, in seconds
+    pub last_sleep_secs: f64,
+    /// Wakeup source that ended the last suspend, if one could be identified
+    pub woken_by: Option<String>,
+    /// Wakeup sources that fired during the last interval
+    pub active: Vec<WakeupEntry>,
+}
+
+fn stats_file(roots: &Roots, name: &str) -> Option<File> {
+    File::open(roots.resolve(SUSPEND_STATS_PATH).join(name)).ok()
+}
+
+/// Open all wakeup sources. Sources come and go with devices, so this is redone after resume.
+fn discover_sources(wakeup_dir: &Path) -> Vec<WakeupSource> {
+    let Ok(dir) = fs::read_dir(wakeup_dir) else {
+        return Vec::new();
+    };
+    let mut sources: Vec<WakeupSource> = dir
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = fs::read_to_string(path.join("name")).ok()?.trim().to_string();
+            let mut source = WakeupSource {
+                name,
+                event_count: File::open(path.join("event_count")).ok()?,
+                wakeup_count: File::open(path.join("wakeup_count")).ok()?,
+                active_time_ms: File::open(path.join("active_time_ms")).ok()?,
+                prev_events: 0,
+                prev_wakeups: 0,
+                prev_active_ms: 0,
+            };
+            // Baseline now so the first sample only reports new activity
+            let mut buf = [0u8; 32];
+            source.prev_events = read_counter(&source.event_count, &mut buf);
+            source.prev_wakeups = read_counter(&source.wakeup_count, &mut buf);
+            source.prev_active_ms = read_counter(&source.active_time_ms, &mut buf);
+            Some(source)
+        })
+        .collect();
+    sources.sort_by(|a, b| a.name.cmp(&b.name));
+    sources
+}
+
+pub fn discover(roots: &Roots) -> SuspendState {
+    let wakeup_dir = roots.resolve(WAKEUP_PATH);
+    SuspendState {
+        success: stats_file(roots, "success"),
+        fail: stats_file(roots, "fail"),
+        last_failed_step: stats_file(roots, "last_failed_step"),
+        sources: discover_sources(&wakeup_dir),
+        wakeup_dir,
+        prev_suspended: suspended_secs(),
+        last_sleep_secs: 0.0,
+        woken_by: None,
+    }
+}
+
+#[inline]
+fn read_counter(file: &File, buf: &mut [u8]) -> u64 {
+    match pread_file(file.as_raw_fd(), buf) {
+        Ok(len) => parse_number_from_line(&buf[..len]),
+        Err(_) => 0,
+    }
+}
+
+/// Sample suspend counters and wakeup source activity.
+/// `None` on kernels without suspend statistics.
+#[inline]
+pub fn collect_suspend(state: &mut SuspendState, buf: &mut [u8]) -> Option<SuspendEntry> {
+    let success = read_counter(state.success.as_ref()?, buf);
+    let fail = state.fail.as_ref().map_or(0, |file| read_counter(file, buf));
+    let last_failed_step = state.last_failed_step.as_ref().and_then(|file| {
+        let len = pread_file(file.as_raw_fd(), buf).ok()?;
+        let step = std::str::from_utf8(&buf[..len]).ok()?.trim();
+        (!step.is_empty()).then(|| step.to_string())
+    });
+
+    let suspended = suspended_secs();
+    let slept = suspended - state.prev_suspended;
+    state.prev_suspended = suspended;
+    let resumed = slept >= MIN_SLEEP_SECS;
+
+    let mut active = Vec::new();
+    let mut culprit: Option<(u64, &str)> = None;
+    for source in state.sources.iter_mut() {
+        let events = read_counter(&source.event_count, buf);
+        let wakeups = read_counter(&source.wakeup_count, buf);
+        let active_ms = read_counter(&source.active_time_ms, buf);
+
+        let event_delta = counter_delta(source.prev_events, events).unwrap_or(0);
+        let wakeup_delta = counter_delta(source.prev_wakeups, wakeups).unwrap_or(0);
+        if event_delta > 0 {
+            active.push(WakeupEntry {
+                name: source.name.clone(),
+                events: event_delta,
+                active_ms: counter_delta(source.prev_active_ms, active_ms).unwrap_or(0),
+            });
+        }
+        // The source that signaled the most wakeups across the suspend woke us
+        if resumed && wakeup_delta > culprit.map_or(0, |(count, _)| count) {
+            culprit = Some((wakeup_delta, &source.name));
+        }
+
+        source.prev_events = events;
+        source.prev_wakeups = wakeups;
+        source.prev_active_ms = active_ms;
+    }
+
+    if resumed {
+        state.last_sleep_secs = slept;
+        state.woken_by = culprit.map(|(_, name)| name.to_string());
+        state.sources = discover_sources(&state.wakeup_dir);
+    }
+
+    Some(SuspendEntry {
+        success,
+        fail,
+        last_failed_step,
+        last_sleep_secs: state.last_sleep_secs,
+        woken_by: state.woken_by.clone(),
+        active,
+    })
+}
+
+/// Suspend counters, last sleep and wakeup source activity ("s")
+#[derive(Default)]
+pub struct SuspendCollector {
+    state: SuspendState,
+    entry: Option<SuspendEntry>,
+    /// Counters and the failed step name hold a single value
+    buf: [u8; 32],
+}
+
+impl Collector for SuspendCollector {
+    fn name(&self) -> &'static str {
+        "suspend"
+    }
+
+    fn key(&self) -> &'static str {
+        "s"
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.state = discover(roots);
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, _elapsed: f64) {
+        self.entry = collect_suspend(&mut self.state, &mut self.buf);
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        let Some(suspend) = &self.entry else {
+            out.push_str("null");
+            return;
+        };
+        out.push('[');
+        itoa_u64(out, suspend.success);
+        out.push(',');
+        itoa_u64(out, suspend.fail);
+        out.push(',');
+        match &suspend.last_failed_step {
+            Some(step) => push_json_str(out, step),
+            None => out.push_str("null"),
+        }
+        out.push(',');
+        ftoa_f64(out, suspend.last_sleep_secs, 1);
+        out.push(',');
+        match &suspend.woken_by {
+            Some(source) => push_json_str(out, source),
+            None => out.push_str("null"),
+        }
+        out.push_str(",[");
+        for (idx, entry) in suspend.active.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push('[');
+            push_json_str(out, &entry.name);
+            out.push(',');
+            itoa_u64(out, entry.events);
+            out.push(',');
+            itoa_u64(out, entry.active_ms);
+            out.push(']');
+        }
+        out.push_str("]]");
+    }
+}
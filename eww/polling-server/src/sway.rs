@@ -0,0 +1,309 @@
+//! Native sway IPC client (i3-ipc framing over the `$SWAYSOCK` unix socket).
+//!
+//! One connection serves both the snapshot requests and the event
+//! subscription, so we never fork `swaymsg` or keep a second socket around.
+
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+use crate::compositor::{LayoutEntry, WindowEntry, WorkspaceEntry, IPC_TIMEOUT};
+use crate::event_loop::EventSource;
+use crate::json::{self, Value};
+use crate::Event;
+
+/// Magic string prefixing every i3-ipc message
+const IPC_MAGIC: &[u8; 6] = b"i3-ipc";
+/// Magic + payload length (u32) + message type (u32)
+const IPC_HEADER_LEN: usize = 14;
+/// Message types (requests and their replies share the same number)
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+const GET_BINDING_STATE: u32 = 12;
+const GET_INPUTS: u32 = 100;
+/// Event messages have the high bit set
+const EVENT_FLAG: u32 = 0x8000_0000;
+const EVENT_WORKSPACE: u32 = EVENT_FLAG;
+const EVENT_MODE: u32 = EVENT_FLAG | 0x2;
+const EVENT_WINDOW: u32 = EVENT_FLAG | 0x3;
+const EVENT_INPUT: u32 = EVENT_FLAG | 0x15;
+/// Events we subscribe to on the shared connection
+const SUBSCRIPTIONS: &[u8] = br#"["workspace","mode","window","input"]"#;
+/// Name of the binding mode sway starts in
+const DEFAULT_MODE: &str = "default";
+
+/// Socket path from the environment, if running inside a sway session.
+pub fn socket_path() -> Option<String> {
+    env::var("SWAYSOCK").ok().filter(|path| !path.is_empty())
+}
+
+/// Blocking i3-ipc connection.
+pub struct SwayIpc {
+    stream: UnixStream,
+    /// Events received while waiting for a reply, delivered before new reads
+    pending: VecDeque<(u32, Vec<u8>)>,
+}
+
+impl SwayIpc {
+    pub fn connect(path: &str) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        // A wedged compositor must not stall the event loop
+        stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+        Ok(Self {
+            stream,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Send a single message. Header fields are in native byte order.
+    pub fn send(&mut self, msg_type: u32, payload: &[u8]) -> io::Result<()> {
+        let mut message = Vec::with_capacity(IPC_HEADER_LEN + payload.len());
+        message.extend_from_slice(IPC_MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&msg_type.to_ne_bytes());
+        message.extend_from_slice(payload);
+        self.stream.write_all(&message)
+    }
+
+    /// Read the next message (reply or event) as `(type, payload)`.
+    fn read_from_socket(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        let mut header = [0u8; IPC_HEADER_LEN];
+        self.stream.read_exact(&mut header)?;
+        if &header[..6] != IPC_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad i3-ipc magic"));
+        }
+        let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+        let msg_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+        let mut payload = vec![0u8; len];
+        self.stream.read_exact(&mut payload)?;
+        Ok((msg_type, payload))
+    }
+
+    /// Send a request and wait for its reply.
+    /// Events arriving in the meantime are queued and handled after the current one.
+    pub fn request(&mut self, msg_type: u32, payload: &[u8]) -> io::Result<Vec<u8>> {
+        self.send(msg_type, payload)?;
+        loop {
+            let (reply_type, reply) = self.read_from_socket()?;
+            if reply_type == msg_type {
+                return Ok(reply);
+            }
+            self.pending.push_back((reply_type, reply));
+        }
+    }
+
+    fn workspaces(&mut self) -> io::Result<Vec<WorkspaceEntry>> {
+        let reply = self.request(GET_WORKSPACES, b"")?;
+        let mut workspaces: Vec<WorkspaceEntry> = json::parse(&reply)
+            .as_ref()
+            .and_then(Value::as_array)
+            .map(|list| list.iter().filter_map(workspace_entry).collect())
+            .unwrap_or_default();
+        workspaces.sort_by_key(|ws| ws.id);
+        Ok(workspaces)
+    }
+
+    fn binding_mode(&mut self) -> io::Result<Option<String>> {
+        let reply = self.request(GET_BINDING_STATE, b"")?;
+        Ok(json::parse(&reply).as_ref().and_then(|state| mode_name(state, "name")))
+    }
+
+    fn focused_window(&mut self) -> io::Result<Option<WindowEntry>> {
+        let reply = self.request(GET_TREE, b"")?;
+        Ok(json::parse(&reply).as_ref().and_then(find_focused).and_then(window_entry))
+    }
+}
+
+/// sway event subscription, waited on by the event loop
+pub struct SwaySource {
+    ipc: SwayIpc,
+}
+
+impl SwaySource {
+    /// Connect and subscribe; the snapshots are taken by `start`.
+    pub fn connect(path: &str) -> io::Result<Self> {
+        let mut ipc = SwayIpc::connect(path)?;
+        // Subscribe before taking snapshots so no change slips in between
+        let reply = ipc.request(SUBSCRIBE, SUBSCRIPTIONS)?;
+        if !command_succeeded(&reply) {
+            return Err(io::Error::other("subscribe rejected"));
+        }
+        Ok(SwaySource { ipc })
+    }
+
+    /// Turn one event message into updates.
+    fn handle(&mut self, msg_type: u32, payload: &[u8], updates: &mut Vec<Event>) -> io::Result<()> {
+        let Some(event) = json::parse(payload) else {
+            return Ok(());
+        };
+        let ipc = &mut self.ipc;
+        match msg_type {
+            EVENT_WORKSPACE => {
+                updates.push(Event::Workspaces(ipc.workspaces()?));
+                // Switching to an empty workspace emits no window event
+                if event.get("change").and_then(Value::as_str) == Some("focus") {
+                    updates.push(Event::Window(ipc.focused_window()?));
+                }
+            }
+            EVENT_MODE => updates.push(Event::Mode(mode_name(&event, "change"))),
+            EVENT_WINDOW => {
+                let change = event.get("change").and_then(Value::as_str).unwrap_or("");
+                let container = event.get("container");
+                let focused = container
+                    .and_then(|c| c.get("focused"))
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                match change {
+                    // Closing the last window leaves focus on the workspace itself
+                    "close" => updates.push(Event::Window(ipc.focused_window()?)),
+                    _ if focused => updates.push(Event::Window(container.and_then(window_entry))),
+                    _ => {}
+                }
+            }
+            EVENT_INPUT => {
+                if let Some(layout) = event.get("input").and_then(keyboard_layout) {
+                    updates.push(Event::Layout(layout));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl EventSource for SwaySource {
+    fn name(&self) -> &'static str {
+        "sway ipc"
+    }
+
+    fn fd(&self) -> RawFd {
+        self.ipc.stream.as_raw_fd()
+    }
+
+    fn start(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        let ipc = &mut self.ipc;
+        let inputs = ipc.request(GET_INPUTS, b"")?;
+        updates.push(Event::Workspaces(ipc.workspaces()?));
+        updates.push(Event::Window(ipc.focused_window()?));
+        updates.push(Event::Mode(ipc.binding_mode()?));
+        if let Some(layout) = json::parse(&inputs).as_ref().and_then(layout_from_inputs) {
+            updates.push(Event::Layout(layout));
+        }
+        // The snapshots supersede anything that happened before them
+        ipc.pending.clear();
+        Ok(())
+    }
+
+    fn ready(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        let (msg_type, payload) = self.ipc.read_from_socket()?;
+        self.handle(msg_type, &payload, updates)?;
+        // Events that arrived while the snapshots above were requested
+        while let Some((msg_type, payload)) = self.ipc.pending.pop_front() {
+            self.handle(msg_type, &payload, updates)?;
+        }
+        Ok(())
+    }
+}
+
+/// Binding mode stored under `key`, or `None` in the default mode.
+fn mode_name(value: &Value, key: &str) -> Option<String> {
+    let name = value.get(key)?.as_str()?;
+    (name != DEFAULT_MODE).then(|| name.to_string())
+}
+
+/// Workspace object from a GET_WORKSPACES reply.
+fn workspace_entry(workspace: &Value) -> Option<WorkspaceEntry> {
+    Some(WorkspaceEntry {
+        id: workspace.get("num")?.as_i64()?,
+        name: workspace.get("name")?.as_str()?.to_string(),
+        focused: workspace.get("focused").and_then(Value::as_bool).unwrap_or(false),
+        urgent: workspace.get("urgent").and_then(Value::as_bool).unwrap_or(false),
+    })
+}
+
+/// Depth-first search for the focused node of a GET_TREE reply.
+fn find_focused(node: &Value) -> Option<&Value> {
+    if node.get("focused").and_then(Value::as_bool) == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(key).and_then(Value::as_array))
+        .flatten()
+        .find_map(find_focused)
+}
+
+/// Window entry for a container; `None` for workspaces and outputs.
+fn window_entry(container: &Value) -> Option<WindowEntry> {
+    let floating = match container.get("type")?.as_str()? {
+        "con" => false,
+        "floating_con" => true,
+        _ => return None,
+    };
+    // Native wayland windows carry app_id, xwayland ones only the X11 class
+    let app_id = container
+        .get("app_id")
+        .and_then(Value::as_str)
+        .or_else(|| container.get("window_properties")?.get("class")?.as_str())
+        .unwrap_or("");
+    Some(WindowEntry {
+        title: container.get("name").and_then(Value::as_str).unwrap_or("").to_string(),
+        app_id: app_id.to_string(),
+        floating,
+        // 0 = none, 1 = output, 2 = global
+        fullscreen: container.get("fullscreen_mode").and_then(Value::as_u64).unwrap_or(0) > 0,
+    })
+}
+
+/// First keyboard with an xkb layout in a GET_INPUTS reply.
+fn layout_from_inputs(inputs: &Value) -> Option<LayoutEntry> {
+    inputs.as_array()?.iter().find_map(keyboard_layout)
+}
+
+/// Active layout of a single input object, if it is a keyboard.
+fn keyboard_layout(input: &Value) -> Option<LayoutEntry> {
+    if input.get("type")?.as_str()? != "keyboard" {
+        return None;
+    }
+    Some(LayoutEntry {
+        name: input.get("xkb_active_layout_name")?.as_str()?.to_string(),
+        index: input.get("xkb_active_layout_index")?.as_u64()? as u32,
+    })
+}
+
+/// Replies to SUBSCRIBE are `{"success":true}`, to RUN_COMMAND an array of those.
+fn command_succeeded(reply: &[u8]) -> bool {
+    let Some(value) = json::parse(reply) else {
+        return false;
+    };
+    let success = |v: &Value| v.get("success").and_then(Value::as_bool).unwrap_or(false);
+    match value.as_array() {
+        Some(results) => results.iter().all(success),
+        None => success(&value),
+    }
+}
+
+/// Run a sway command on a fresh connection.
+fn run_command(path: &str, command: &str) -> io::Result<()> {
+    let mut ipc = SwayIpc::connect(path)?;
+    let reply = ipc.request(RUN_COMMAND, command.as_bytes())?;
+    if command_succeeded(&reply) {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&reply).into_owned()))
+    }
+}
+
+/// Switch every keyboard to another layout (`next`, `prev` or an index).
+pub fn switch_layout(path: &str, target: &str) -> io::Result<()> {
+    run_command(path, &format!("input type:keyboard xkb_switch_layout {}", target))
+}
+
+/// Focus a workspace by number.
+pub fn focus_workspace(path: &str, id: i64) -> io::Result<()> {
+    run_command(path, &format!("workspace number {}", id))
+}
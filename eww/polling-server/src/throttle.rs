@@ -0,0 +1,181 @@
+//! Per-CPU thermal throttling: throttle event counters and frequency ratio.
+//!
+//! Entries use the same "cpuN" ids as `CpuEntry`, so the bar can tell busy
+//! cores apart from busy *and* throttled ones.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::rc::Rc;
+
+use crate::collector::Collector;
+use crate::source::Roots;
+use crate::{counter_delta, itoa_u64, itoa_usize, parse_number_from_line, pread_file, push_opt_u32};
+
+/// Directory holding cpuN subdirectories
+const CPU_SYSFS_PATH: &str = "/sys/devices/system/cpu";
+
+/// Open counters for one logical CPU
+pub struct ThrottleCpu {
+    /// CPU identifier (e.g., "cpu0"), shared with the entries
+    id: Rc<str>,
+    /// CPU number, for ordering
+    index: usize,
+    /// thermal_throttle/core_throttle_count
+    core_file: Option<File>,
+    /// thermal_throttle/package_throttle_count (same value on every CPU of a package)
+    package_file: Option<File>,
+    /// cpufreq/scaling_cur_freq in kHz
+    freq_file: Option<File>,
+    /// cpufreq/cpuinfo_max_freq in kHz, read once
+    max_freq_khz: u64,
+    /// Previous counter values
+    prev_core: Option<u64>,
+    prev_package: Option<u64>,
+}
+
+/// Throttling entry for output
+pub struct ThrottleEntry {
+    /// CPU identifier matching `CpuEntry::id`
+    pub id: Rc<str>,
+    /// Core throttle events during the last interval
+    pub core_events: u64,
+    /// Package throttle events during the last interval
+    pub package_events: u64,
+    /// Current frequency relative to the maximum (0-100), `None` when either is unknown
+    pub freq_percent: Option<u32>,
+}
+
+/// Open throttle and frequency files of every CPU, sorted by CPU number.
+/// CPUs exposing neither (e.g., VMs without thermal or cpufreq drivers) are skipped.
+pub fn discover(roots: &Roots) -> Vec<ThrottleCpu> {
+    let Ok(dir) = fs::read_dir(roots.resolve(CPU_SYSFS_PATH)) else {
+        return Vec::new();
+    };
+    let mut cpus: Vec<ThrottleCpu> = dir
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name();
+            let index = file_name.to_str()?.strip_prefix("cpu")?.parse::<usize>().ok()?;
+            let path = entry.path();
+
+            let core_file = File::open(path.join("thermal_throttle/core_throttle_count")).ok();
+            let package_file = File::open(path.join("thermal_throttle/package_throttle_count")).ok();
+            let freq_file = File::open(path.join("cpufreq/scaling_cur_freq")).ok();
+            let max_freq_khz = fs::read(path.join("cpufreq/cpuinfo_max_freq"))
+                .map(|data| parse_number_from_line(&data))
+                .unwrap_or(0);
+            if core_file.is_none() && freq_file.is_none() {
+                return None;
+            }
+
+            let mut id = String::with_capacity(8);
+            id.push_str("cpu");
+            itoa_usize(&mut id, index);
+            Some(ThrottleCpu {
+                id: id.into(),
+                index,
+                core_file,
+                package_file,
+                freq_file,
+                max_freq_khz,
+                prev_core: None,
+                prev_package: None,
+            })
+        })
+        .collect();
+    cpus.sort_by_key(|cpu| cpu.index);
+    cpus
+}
+
+/// Read one counter file, `None` if missing or unreadable (e.g., CPU went offline).
+#[inline]
+fn read_counter(file: Option<&File>, buf: &mut [u8]) -> Option<u64> {
+    let len = pread_file(file?.as_raw_fd(), buf).ok()?;
+    Some(parse_number_from_line(&buf[..len]))
+}
+
+/// Sample throttle counters and frequencies.
+/// Event counts are deltas since the previous sample, 0 on the first one.
+#[inline]
+pub fn collect_throttle(cpus: &mut [ThrottleCpu], buf: &mut [u8], entries: &mut Vec<ThrottleEntry>) {
+    for cpu in cpus.iter_mut() {
+        let core = read_counter(cpu.core_file.as_ref(), buf);
+        let package = read_counter(cpu.package_file.as_ref(), buf);
+        let freq_khz = read_counter(cpu.freq_file.as_ref(), buf);
+
+        let core_events = match (core, cpu.prev_core) {
+            (Some(now), Some(prev)) => counter_delta(prev, now).unwrap_or(0),
+            _ => 0,
+        };
+        let package_events = match (package, cpu.prev_package) {
+            (Some(now), Some(prev)) => counter_delta(prev, now).unwrap_or(0),
+            _ => 0,
+        };
+        cpu.prev_core = core;
+        cpu.prev_package = package;
+
+        let freq_percent = match freq_khz {
+            Some(freq) if cpu.max_freq_khz > 0 => Some((freq * 100 / cpu.max_freq_khz).min(100) as u32),
+            _ => None,
+        };
+
+        entries.push(ThrottleEntry {
+            id: Rc::clone(&cpu.id),
+            core_events,
+            package_events,
+            freq_percent,
+        });
+    }
+}
+
+/// Per-CPU throttle events and frequency ratio ("h")
+#[derive(Default)]
+pub struct ThrottleCollector {
+    cpus: Vec<ThrottleCpu>,
+    entries: Vec<ThrottleEntry>,
+    /// Throttle counts and frequencies hold a single value
+    buf: [u8; 32],
+}
+
+impl Collector for ThrottleCollector {
+    fn name(&self) -> &'static str {
+        "throttle"
+    }
+
+    fn key(&self) -> &'static str {
+        "h"
+    }
+
+    fn init(&mut self, roots: &Roots) -> io::Result<()> {
+        self.cpus = discover(roots);
+        self.entries = Vec::with_capacity(self.cpus.len());
+        Ok(())
+    }
+
+    #[inline]
+    fn sample(&mut self, _elapsed: f64) {
+        self.entries.clear();
+        collect_throttle(&mut self.cpus, &mut self.buf, &mut self.entries);
+    }
+
+    #[inline]
+    fn serialize(&self, out: &mut String) {
+        out.push('[');
+        for (idx, entry) in self.entries.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str("[\"");
+            out.push_str(&entry.id);
+            out.push_str("\",");
+            itoa_u64(out, entry.core_events);
+            out.push(',');
+            itoa_u64(out, entry.package_events);
+            out.push(',');
+            push_opt_u32(out, entry.freq_percent);
+            out.push(']');
+        }
+        out.push(']');
+    }
+}
@@ -0,0 +1,125 @@
+//! Kernel uevents from the NETLINK_KOBJECT_UEVENT socket.
+//!
+//! Collectors name the subsystem they care about (e.g., battery status changes
+//! under "power_supply"); a matching uevent makes the main loop sample them at
+//! once instead of waiting for their next period.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use crate::event_loop::EventSource;
+use crate::events::Event;
+
+/// Multicast group of uevents sent by the kernel (udev re-broadcasts on group 2)
+const KERNEL_GROUP: u32 = 1;
+/// uevent messages are capped at 8 KiB by the kernel
+const MESSAGE_SIZE: usize = 8192;
+
+/// Non-blocking socket subscribed to kernel uevents.
+pub fn open() -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            libc::NETLINK_KOBJECT_UEVENT,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = KERNEL_GROUP;
+    let rc = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
+/// Subsystem of a uevent message if it is one of `subsystems`.
+/// Messages are `action@devpath` followed by NUL-separated `KEY=value` pairs.
+pub fn match_subsystem(message: &[u8], subsystems: &[&'static str]) -> Option<&'static str> {
+    let value = message
+        .split(|&b| b == 0)
+        .find_map(|field| field.strip_prefix(b"SUBSYSTEM="))?;
+    subsystems.iter().copied().find(|subsystem| subsystem.as_bytes() == value)
+}
+
+/// Netlink socket pushing `Event::Uevent` for the subsystems collectors listen to
+pub struct UeventSource {
+    socket: OwnedFd,
+    subsystems: Vec<&'static str>,
+    buf: Vec<u8>,
+}
+
+impl UeventSource {
+    pub fn new(subsystems: Vec<&'static str>) -> io::Result<Self> {
+        Ok(UeventSource {
+            socket: open()?,
+            subsystems,
+            buf: vec![0u8; MESSAGE_SIZE],
+        })
+    }
+}
+
+impl EventSource for UeventSource {
+    fn name(&self) -> &'static str {
+        "uevent"
+    }
+
+    fn fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+
+    fn ready(&mut self, updates: &mut Vec<Event>) -> io::Result<()> {
+        loop {
+            let len = unsafe {
+                libc::recv(
+                    self.socket.as_raw_fd(),
+                    self.buf.as_mut_ptr() as *mut libc::c_void,
+                    self.buf.len(),
+                    0,
+                )
+            };
+            if len < 0 {
+                // ENOBUFS: a burst overflowed the socket buffer; later events still arrive
+                return match io::Error::last_os_error().raw_os_error() {
+                    Some(libc::EAGAIN) | Some(libc::ENOBUFS) => Ok(()),
+                    Some(libc::EINTR) => continue,
+                    _ => Err(io::Error::last_os_error()),
+                };
+            }
+            if let Some(subsystem) = match_subsystem(&self.buf[..len as usize], &self.subsystems) {
+                // Several uevents in one wakeup still need only one sample
+                if !updates.iter().any(|update| matches!(update, Event::Uevent(s) if *s == subsystem)) {
+                    updates.push(Event::Uevent(subsystem));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_subsystem_field() {
+        let message = b"change@/devices/LNXSYSTM:00/PNP0C0A:00/power_supply/BAT0\0ACTION=change\0\
+                        DEVPATH=/devices/LNXSYSTM:00/PNP0C0A:00/power_supply/BAT0\0SUBSYSTEM=power_supply\0\
+                        POWER_SUPPLY_NAME=BAT0\0SEQNUM=4242\0";
+        assert_eq!(match_subsystem(message, &["net", "power_supply"]), Some("power_supply"));
+        assert_eq!(match_subsystem(message, &["net"]), None);
+        assert_eq!(match_subsystem(b"add@/devices/virtual/net/tun0\0ACTION=add\0", &["net"]), None);
+    }
+}
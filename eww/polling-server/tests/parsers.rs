@@ -0,0 +1,269 @@
+//! Regression tests for the /proc parsers and the payload, run against samples
+//! recorded from different machines.
+//!
+//! Every machine under `tests/fixtures` has two /proc snapshots taken one second
+//! apart (`before`, `after`), the sysfs files the CPU and battery collectors read
+//! (`sys`) and the expected output (`golden`). After an intended output change,
+//! rewrite the golden files with `UPDATE_GOLDEN=1 cargo test` and review the diff.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use polling_server::battery::{collect_battery, discover};
+use polling_server::config::Config;
+use polling_server::cpu::{collect_cpu, parse_cpu_list};
+use polling_server::disk::parse_disks;
+use polling_server::memory::collect_memory;
+use polling_server::network::parse_network;
+use polling_server::source::Roots;
+use polling_server::{build_payload, EventState, Registry};
+
+/// nvme with partitions, wifi, docker bridges and veths, a VPN going down and wireguard coming up
+const LAPTOP: &str = "laptop-nvme";
+/// eMMC and SD card (mmcblk) with boot partitions, zram swap
+const SBC: &str = "sbc-mmcblk";
+/// 64 of 128 possible cores with two taken offline, md RAID over SATA, many docker bridges
+const SERVER: &str = "server-64core";
+const MACHINES: &[&str] = &[LAPTOP, SBC, SERVER];
+
+/// /proc files of a snapshot, relative to the proc root
+const PROC_FILES: &[&str] = &["stat", "meminfo", "net/dev", "diskstats"];
+const PHASES: &[&str] = &["before", "after"];
+/// Collectors reading the recorded files; the others depend on the host
+const FIXTURE_COLLECTORS: &[&str] = &["cpu", "memory", "network", "disk"];
+
+fn fixture(machine: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(machine)
+}
+
+fn read(machine: &str, phase: &str, file: &str) -> Vec<u8> {
+    let path = fixture(machine).join(phase).join(file);
+    fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+/// Compare `actual` with `golden/<name>` of `machine`, or rewrite it under `UPDATE_GOLDEN`.
+fn check_golden(machine: &str, name: &str, actual: &str) {
+    let path = fixture(machine).join("golden").join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("{}: {} (UPDATE_GOLDEN=1 creates it)", path.display(), err));
+    assert!(actual == expected, "{} differs:\n--- expected\n{}--- actual\n{}", path.display(), expected, actual);
+}
+
+fn without_trailing_newline(data: &[u8]) -> &[u8] {
+    data.strip_suffix(b"\n").expect("fixture should end with a newline")
+}
+
+fn render_cpu(machine: &str, after: &[u8]) -> String {
+    let present = fs::read(fixture(machine).join("sys/devices/system/cpu/present")).unwrap();
+    let present = parse_cpu_list(&present);
+    let mut prev = Vec::new();
+    let mut entries = Vec::new();
+    collect_cpu(&read(machine, "before", "stat"), &mut prev, &present, &mut entries);
+    entries.clear();
+    collect_cpu(after, &mut prev, &present, &mut entries);
+
+    let mut out = String::new();
+    for entry in &entries {
+        match entry.usage {
+            Some(usage) => writeln!(out, "{} usage={} reset={}", entry.id, usage, entry.reset).unwrap(),
+            None => writeln!(out, "{} offline", entry.id).unwrap(),
+        }
+    }
+    out
+}
+
+fn render_memory(data: &[u8]) -> String {
+    match collect_memory(data) {
+        Some(entry) => format!(
+            "total_kib={} available_kib={} used_percent={:.3}\n",
+            entry.total_kib, entry.available_kib, entry.used_percent
+        ),
+        None => "none\n".to_string(),
+    }
+}
+
+fn render_counters(parsed: &[(&str, u64, u64)], first: &str, second: &str) -> String {
+    let mut out = String::new();
+    for (name, a, b) in parsed {
+        writeln!(out, "{} {}={} {}={}", name, first, a, second, b).unwrap();
+    }
+    out
+}
+
+fn render_network(data: &[u8]) -> String {
+    render_counters(&parse_network(data), "rx_bytes", "tx_bytes")
+}
+
+fn render_disks(data: &[u8]) -> String {
+    render_counters(&parse_disks(data), "read_sectors", "write_sectors")
+}
+
+/// Render both snapshots of `file`, one section per phase.
+fn render_phases(machine: &str, file: &str, render: fn(&[u8]) -> String) -> String {
+    let mut out = String::new();
+    for phase in PHASES {
+        writeln!(out, "[{}]", phase).unwrap();
+        out.push_str(&render(&read(machine, phase, file)));
+    }
+    out
+}
+
+/// Copy the `phase` snapshot into `proc`, overwriting files in place so the
+/// collectors' open descriptors see the new contents.
+fn install(machine: &str, phase: &str, proc: &Path) {
+    for file in PROC_FILES {
+        let path = proc.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, read(machine, phase, file)).unwrap();
+    }
+}
+
+/// Payload after sampling the two snapshots one second apart.
+fn render_payload(machine: &str) -> String {
+    let proc = env::temp_dir().join(format!("polling-server-fixture-{}-{}", machine, std::process::id()));
+    let _ = fs::remove_dir_all(&proc);
+    install(machine, "before", &proc);
+
+    let mut registry = Registry::with_defaults();
+    let mut config = Config::default();
+    let names: Vec<&str> = registry.names().collect();
+    for name in names {
+        config.collector_mut(name).enabled = FIXTURE_COLLECTORS.contains(&name);
+    }
+    registry.configure(&config);
+    registry.init(&Roots {
+        proc: proc.clone(),
+        sys: fixture(machine).join("sys"),
+    });
+
+    let start = Instant::now();
+    registry.sample_all(start);
+    install(machine, "after", &proc);
+    registry.sample_all(start + Duration::from_secs(1));
+
+    let mut payload = String::new();
+    build_payload(&mut payload, &registry, &EventState::default());
+    fs::remove_dir_all(&proc).unwrap();
+    payload + "\n"
+}
+
+#[test]
+fn cpu_matches_golden() {
+    for machine in MACHINES {
+        check_golden(machine, "cpu.txt", &render_cpu(machine, &read(machine, "after", "stat")));
+    }
+}
+
+#[test]
+fn memory_matches_golden() {
+    for machine in MACHINES {
+        check_golden(machine, "memory.txt", &render_phases(machine, "meminfo", render_memory));
+    }
+}
+
+#[test]
+fn network_matches_golden() {
+    for machine in MACHINES {
+        check_golden(machine, "network.txt", &render_phases(machine, "net/dev", render_network));
+    }
+}
+
+#[test]
+fn disks_match_golden() {
+    for machine in MACHINES {
+        check_golden(machine, "disks.txt", &render_phases(machine, "diskstats", render_disks));
+    }
+}
+
+#[test]
+fn payload_matches_golden() {
+    for machine in MACHINES {
+        check_golden(machine, "payload.json", &render_payload(machine));
+    }
+}
+
+/// The parsers scan for '\n' and treat the last byte as the end of a line too;
+/// the final line has to parse the same whether or not it is terminated.
+#[test]
+fn last_line_without_newline() {
+    for machine in MACHINES {
+        let stat = read(machine, "after", "stat");
+        assert_eq!(render_cpu(machine, without_trailing_newline(&stat)), render_cpu(machine, &stat));
+
+        let meminfo = read(machine, "after", "meminfo");
+        assert_eq!(render_memory(without_trailing_newline(&meminfo)), render_memory(&meminfo));
+
+        let net_dev = read(machine, "after", "net/dev");
+        assert_eq!(render_network(without_trailing_newline(&net_dev)), render_network(&net_dev));
+
+        let diskstats = read(machine, "after", "diskstats");
+        assert_eq!(render_disks(without_trailing_newline(&diskstats)), render_disks(&diskstats));
+    }
+
+    // The value the parsers look for sits on the unterminated line
+    assert_eq!(
+        render_memory(b"MemTotal:        1000 kB\nMemAvailable:     250 kB"),
+        "total_kib=1000 available_kib=250 used_percent=75.000\n"
+    );
+    let net_dev = b"Inter-|   Receive\n face |bytes\n  eth0: 100 1 0 0 0 0 0 0 200 2 0 0 0 0 0 0";
+    assert_eq!(render_network(net_dev), "eth0 rx_bytes=100 tx_bytes=200\n");
+    // Truncated after the transmit bytes, the last field parsed
+    let net_dev = b"Inter-|   Receive\n face |bytes\n  eth0: 100 1 0 0 0 0 0 0 200";
+    assert_eq!(render_network(net_dev), "eth0 rx_bytes=100 tx_bytes=200\n");
+    let mut prev = Vec::new();
+    let mut entries = Vec::new();
+    collect_cpu(b"cpu  1 2 3 4\ncpu0 1 0 0 4 0 0 0 0 0 0", &mut prev, &[0], &mut entries);
+    collect_cpu(b"cpu  1 2 3 4\ncpu0 3 0 0 6 0 0 0 0 0 0", &mut prev, &[0], &mut entries);
+    assert_eq!(entries[1].usage, Some(50));
+}
+
+/// Empty and single-byte files (e.g., a read cut short) must not panic.
+#[test]
+fn empty_and_truncated_files() {
+    for data in [&b""[..], b"\n", b"c", b"cpu0"] {
+        let mut entries = Vec::new();
+        collect_cpu(data, &mut Vec::new(), &[], &mut entries);
+        assert!(entries.is_empty());
+        assert!(collect_memory(data).is_none());
+        assert!(parse_network(data).is_empty());
+        assert!(parse_disks(data).is_empty());
+    }
+}
+
+/// USB-C-only laptops have no "Mains" supply; the UCSI port charging them counts as AC.
+#[test]
+fn usb_c_charger_counts_as_ac() {
+    let roots = Roots {
+        proc: PathBuf::new(),
+        sys: fixture(LAPTOP).join("sys"),
+    };
+    let entry = collect_battery(&discover(&roots), &mut [0; 64]).expect("the laptop has a battery");
+    assert_eq!((entry.capacity, entry.status.as_deref()), (Some(87), Some("Not charging")));
+    assert_eq!(entry.end_threshold, Some(80));
+    assert!(entry.ac_online);
+
+    // Without any adapter or charger, only a discharging battery means running on it
+    let sys = env::temp_dir().join(format!("polling-server-battery-{}", std::process::id()));
+    let battery = sys.join("class/power_supply/BAT0");
+    fs::create_dir_all(&battery).unwrap();
+    fs::write(battery.join("type"), "Battery\n").unwrap();
+    fs::write(battery.join("capacity"), "40\n").unwrap();
+    let roots = Roots {
+        proc: PathBuf::new(),
+        sys: sys.clone(),
+    };
+    for (status, ac_online) in [("Discharging", false), ("Not charging", true)] {
+        fs::write(battery.join("status"), format!("{}\n", status)).unwrap();
+        let entry = collect_battery(&discover(&roots), &mut [0; 64]).unwrap();
+        assert_eq!(entry.ac_online, ac_online, "{}", status);
+    }
+    fs::remove_dir_all(&sys).unwrap();
+}
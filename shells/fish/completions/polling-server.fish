@@ -0,0 +1,25 @@
+# polling-server completions, generated by `polling-server completions fish`
+complete -c polling-server -f
+set -l commands run once list-collectors check-config completions layout workspace profile thresholds help
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a run -d 'Stream a payload per poll and on desktop events (default)'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a once -d 'Print a single payload and exit'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a list-collectors -d 'List collectors with their payload key and options'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a check-config -d 'Validate the config file and exit'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a completions -d 'Print shell completions'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a layout -d 'Switch the keyboard layout'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a workspace -d 'Focus a workspace'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a profile -d 'Switch power profile, governor and EPP'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a thresholds -d 'Set battery charge thresholds'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands" -a help -d 'Show this help'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config" -l interval -x -d 'Sampling interval in milliseconds of every collector without its own interval_ms'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config" -l format -x -a 'json pretty' -d 'Output format: json or pretty'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config" -l collectors -x -a 'cpu memory network disk power throttle profile battery suspend' -d 'Comma-separated collectors to enable, others are disabled'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config" -l title-width -x -d 'Maximum window title length, 0 disables truncation'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config" -l adaptive -x -a 'on off' -d 'Poll slower on battery, when idle or when the reader is blocked'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config" -l config -r -F -d 'Config file instead of ~/.config/eww/polling-server.toml'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config profile thresholds" -l proc-root -x -a '(__fish_complete_directories)' -d 'Read procfs from DIR instead of /proc (e.g., /host/proc)'
+complete -c polling-server -n "not __fish_seen_subcommand_from $commands; or __fish_seen_subcommand_from run once check-config profile thresholds" -l sys-root -x -a '(__fish_complete_directories)' -d 'Read sysfs from DIR instead of /sys (e.g., /host/sys)'
+complete -c polling-server -s h -l help -d 'Show help'
+complete -c polling-server -n '__fish_seen_subcommand_from completions' -a 'fish'
+complete -c polling-server -n '__fish_seen_subcommand_from layout' -a 'next prev'
+complete -c polling-server -n '__fish_seen_subcommand_from profile' -a 'next low-power balanced performance'